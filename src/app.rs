@@ -1,13 +1,99 @@
-use std::{self, time, collections::HashMap};
+use std::{self, collections::HashMap, path::PathBuf};
+
+use keybrah::{
+    clock::{Clock, SystemClock},
+    engine::{Engine, ErrorPolicy, KeyInput},
+};
+
+use crate::{
+    history::{self, History, TestResult},
+    replay::{self, GhostChoice, Player, Replay, Replays},
+    keyboard::{Geometry, KeyLayout, Keyboard},
+    net::Race,
+    report::Report,
+    screen,
+    source::{Sources, TextMeta, TextRequest, DEFAULT_SOURCE},
+    ui::Wrapped,
+    words::{self, TextOptions},
+};
 
 // I guess we're using enums as states 
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum CurrentScreen {
     Main,
     Editing,
     Stats,
+    Replay,
+    /// Prompt for the seed of the next text.
+    Seed,
     Exiting,
 }
 
+impl CurrentScreen {
+    /// Whether the app may move from this screen to `next`, anything else
+    /// is refused by `App::go`.
+    pub fn can_go(self, next: CurrentScreen) -> bool {
+        use CurrentScreen::*;
+        match (self, next) {
+            // every screen can be left back to Main
+            (_, Main) => true,
+            (Main | Stats, Editing | Replay | Exiting) => true,
+            (Main, Seed) => true,
+            (Editing, Stats) => true,
+            _ => false,
+        }
+    }
+}
+
+pub const DEFAULT_PACE_WPM: u16 = 60;
+
+/// Length of a regular generated text.
+pub const TEST_WORDS: usize = 10;
+
+/// Length of the daily challenge text.
+pub const DAILY_WORDS: usize = 30;
+
+/// Timed texts are long enough to last at this speed.
+pub const TIMED_MAX_WPM: u64 = 240;
+
+/// What the pace caret runs at.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Pace {
+    Off,
+    Wpm(u16),
+    Average,
+    Best,
+}
+
+impl Pace {
+    pub fn next(&self) -> Pace {
+        match self {
+            Pace::Off => Pace::Wpm(DEFAULT_PACE_WPM),
+            Pace::Wpm(_) => Pace::Average,
+            Pace::Average => Pace::Best,
+            Pace::Best => Pace::Off,
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Pace::Off => "off".to_string(),
+            Pace::Wpm(wpm) => wpm.to_string(),
+            Pace::Average => "avg".to_string(),
+            Pace::Best => "best".to_string(),
+        }
+    }
+}
+
+/// How a finished run compares to its ghost, negative is ahead.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GhostDelta {
+    /// Finishing times apart.
+    Ms(i64),
+    /// Chars apart when the time of a timed test ran out.
+    Chars(i64),
+}
+
 pub enum CurrentlyEditing {
     Key,
     Value,
@@ -16,55 +102,105 @@ pub enum CurrentlyEditing {
 pub struct App {
     pub key_input: String,
     pub value_input: String,
-    pub cursor: char,
+    /// Digits typed at the seed prompt.
+    pub seed_input: String,
     pub pairs: HashMap<String, String>,
     pub current_screen: CurrentScreen,
     // if no editing is happening => None
     pub currently_editing: Option<CurrentlyEditing>, 
-    pub current_text: String,
     pub original_text: String,  
-    pub wrong: bool,
-    pub mistakes: u16,
-    pub accuracy: u16,
-    pub right_finger_map: HashMap<char, char>,
-    pub left_finger_map: HashMap<char, char>,
-    pub right_nums: Vec<char>,
-    pub left_nums: Vec<char>,
-    pub rights: Vec<char>,
-    pub lefts: Vec<char>,
-    pub start_time: Option<time::Instant>,
-    pub time: Option<time::Duration>,
+    /// The test of `original_text`, everything typed and what it adds up to.
+    pub engine: Engine,
+    /// `original_text` cut into rows for the playground.
+    pub wrapped: Wrapped,
+    // no correctness colors until the Stats screen
+    pub blind: bool,
+    pub pace: Pace,
+    pub history: History,
+    pub daily_results: History,
+    /// Date of the daily challenge being played, the regular text and time
+    /// limit wait in `regular_*` meanwhile.
+    pub daily: Option<String>,
+    pub regular_text: String,
+    pub regular_meta: TextMeta,
+    pub regular_time_limit: Option<u64>,
+    /// Where the current text came from, with its seed if it can be made
+    /// again.
+    pub text_meta: TextMeta,
+    pub text_options: TextOptions,
+    /// Length of a regular text.
+    pub words: usize,
+    /// Seconds a timed test runs for, `None` for tests that end with the
+    /// text.
+    pub time_limit: Option<u64>,
+    pub sources: Sources,
+    /// Id of the source regular texts come from.
+    pub source: String,
+    pub replays: Replays,
+    /// Where results and replays are stored, `None` keeps them in memory.
+    pub data_dir: Option<PathBuf>,
+    /// The last finished test, printed on exit with `--json`.
+    pub report: Option<Report>,
+    pub ghost_choice: GhostChoice,
+    // picked when the test starts so this run can't race itself
+    pub ghost: Option<Replay>,
+    pub player: Option<Player>,
+    // mismatches found when the replay on screen was checked
+    pub replay_issues: Vec<String>,
+    // one-line message shown next to the title
+    pub status: Option<String>,
+    /// The LAN race this instance hosts or joined.
+    pub race: Option<Race>,
+    pub keyboard: Keyboard,
+    /// Where every timestamp of the session comes from.
+    pub clock: Box<dyn Clock>,
+    /// `clock` reading when the test started.
+    pub start_ms: Option<u64>,
     pub wpm: Option<u16>
 }
 
 impl App {
     /// Creates a new `App` that holds states and temp inputs.
     pub fn new() -> App {
-        App { key_input: String::new(),
+        let mut app = App { key_input: String::new(),
               value_input: String::new(),
-              cursor: ' ',
+              seed_input: String::new(),
               pairs: HashMap::new(),
               current_screen: CurrentScreen::Main,
               currently_editing: None,
-              original_text: "one time leave part out who take all same ask should".to_string(),
-              current_text: "one time leave part out who take all same ask should".to_string(),
-              wrong: false,
-              mistakes: 0,
-              accuracy: 100,
-              right_finger_map: HashMap::new(),
-              left_finger_map: HashMap::new(),
-              right_nums: vec![],
-              left_nums: vec![],
-              rights: vec![],
-              lefts: vec![],
-              start_time: None,
-              time: None,
+              original_text: String::new(),
+              engine: Engine::new("", ErrorPolicy::StopOnLetter),
+              wrapped: Wrapped::default(),
+              blind: false,
+              pace: Pace::Off,
+              history: History::default(),
+              daily_results: History::default(),
+              daily: None,
+              regular_text: String::new(),
+              regular_meta: TextMeta::default(),
+              regular_time_limit: None,
+              text_meta: TextMeta::default(),
+              text_options: TextOptions::default(),
+              words: TEST_WORDS,
+              time_limit: None,
+              sources: Sources::builtin(),
+              source: DEFAULT_SOURCE.to_string(),
+              replays: Replays::default(),
+              data_dir: History::data_dir(),
+              report: None,
+              ghost_choice: GhostChoice::Off,
+              ghost: None,
+              player: None,
+              replay_issues: vec![],
+              status: None,
+              race: None,
+              keyboard: Keyboard::new(Geometry::Ansi, KeyLayout::qwerty()),
+              clock: Box::new(SystemClock::new()),
+              start_ms: None,
               wpm: None,
-        }
-    }
-
-    pub fn edit_text(&mut self, text: &str) {
-        self.current_text = text.to_string();
+        };
+        app.set_seed(words::random_seed());
+        app
     }
 
     pub fn save_key_value(&mut self) {
@@ -86,89 +222,524 @@ impl App {
         } else {self.currently_editing = Some(CurrentlyEditing::Key)};
     }
 
-    pub fn go_stats(&mut self) {
-        self.current_screen = CurrentScreen::Stats;
+    /// Moves to the `next` screen, running the exit hook of this one and
+    /// the enter hook of the next. False when the move isn't allowed.
+    pub fn go(&mut self, next: CurrentScreen) -> bool {
+        if !self.current_screen.can_go(next) {
+            return false;
+        }
+        screen::handler(self.current_screen).on_exit(self);
+        self.current_screen = next;
+        screen::handler(next).on_enter(self);
+        true
+    }
+
+    /// Starts the clock on a fresh test, done on entering Editing.
+    pub fn start_test(&mut self) {
+        self.start_ms = Some(self.clock.now_ms());
+        self.currently_editing = Some(CurrentlyEditing::Key);
+        self.engine.reset();
+        self.ghost = self.replays.ghost(&self.original_text, self.ghost_choice).cloned();
+    }
+
+    /// Starts a test, in a race the host starts it for everyone and the
+    /// others wait for the host.
+    pub fn begin(&mut self) {
+        match &self.race {
+            Some(race) if !race.is_host => return,
+            Some(race) => race.start(&self.original_text),
+            None => {}
+        }
+        self.go(CurrentScreen::Editing);
     }
 
-    pub fn update_accuracy(&mut self) {
-        let ori_t: Vec<char> = self.original_text.chars().collect();
-        let ori_len = ori_t.len() as f32;
-        let correct = (ori_len - (self.mistakes as f32)) as f32;
-        self.accuracy = ((correct / ori_len) * 100.0) as u16;
+    /// Replaces the text to type, e.g. with the one a race host sent.
+    pub fn set_text(&mut self, text: &str, meta: TextMeta) {
+        self.original_text = text.to_string();
+        self.text_meta = meta;
+        self.engine = Engine::new(text, self.engine.policy());
+        self.wrapped.clear();
     }
 
-    pub fn update_wpm(&mut self) {
-        self.time = Some(self.start_time.unwrap().elapsed());
-        let time = self.time.unwrap().as_secs_f64();
-        let cnums: f64 = self.key_input.len() as f64;
-        let numinator = (cnums - (self.mistakes as f64)) * 60.0;
-        self.wpm = Some((numinator / (5.0 * time)) as u16);
+    /// Asks the source `id` for a text, a source that fails leaves the
+    /// current text and says why in the status line.
+    fn load_text(&mut self, id: &str, request: &TextRequest) -> bool {
+        match self.sources.generate(id, request) {
+            Ok((text, meta)) => {
+                self.set_text(&text, meta);
+                true
+            }
+            Err(err) => {
+                self.status = Some(format!("text source {id} failed: {err}"));
+                false
+            }
+        }
     }
 
+    /// Generates the regular text from `seed`, leaving the daily challenge.
+    /// False if the source failed, the old text stays and `status` says why.
+    pub fn set_seed(&mut self, seed: u64) -> bool {
+        self.daily = None;
+        let request = TextRequest {
+            mode: if self.time_limit.is_some() { "time" } else { "words" },
+            seed,
+            words: match self.time_limit {
+                Some(secs) => ((secs.saturating_mul(TIMED_MAX_WPM) / 60) as usize).max(self.words),
+                None => self.words,
+            },
+            options: self.text_options,
+            allowed: self.keyboard.chars(),
+        };
+        let source = self.source.clone();
+        self.load_text(&source, &request)
+    }
+
+    /// Regenerates the regular text after the text options changed, the
+    /// seed stays so only the punctuation and numbers differ.
+    pub fn regenerate(&mut self) -> bool {
+        self.daily.is_some() || self.set_seed(self.text_meta.seed.unwrap_or_else(words::random_seed))
+    }
+
+    /// Switches between today's challenge and the regular text.
+    pub fn toggle_daily(&mut self) {
+        if self.daily.take().is_some() {
+            let text = std::mem::take(&mut self.regular_text);
+            let meta = std::mem::take(&mut self.regular_meta);
+            self.set_text(&text, meta);
+            self.time_limit = self.regular_time_limit.take();
+        } else {
+            let date = history::today();
+            // the same plain text for everyone, whatever their own settings
+            let request = TextRequest {
+                mode: "daily",
+                seed: words::seed_from(&date),
+                words: DAILY_WORDS,
+                options: TextOptions::default(),
+                allowed: self.keyboard.chars(),
+            };
+            let text = self.original_text.clone();
+            let meta = self.text_meta.clone();
+            if self.load_text(DEFAULT_SOURCE, &request) {
+                self.regular_text = text;
+                self.regular_meta = meta;
+                // everyone types the whole text, so all runs compare
+                self.regular_time_limit = self.time_limit.take();
+                self.daily = Some(date);
+            }
+        }
+    }
+
+    /// Best run of today's challenge under the current error policy.
+    pub fn todays_best(&self) -> Option<u16> {
+        self.daily_results.best(&self.daily_mode(&history::today()))
+    }
+
+    fn daily_mode(&self, date: &str) -> String {
+        format!("daily {date} · {}", self.engine.policy().name())
+    }
+
+    /// Where results of the current text are kept.
+    pub fn results(&self) -> &History {
+        if self.daily.is_some() { &self.daily_results } else { &self.history }
+    }
+
+    /// Picks up a race the host started.
+    pub fn poll_race(&mut self) {
+        let Some(race) = &self.race else { return };
+        if race.closed() {
+            self.status = Some("the host left the race".to_string());
+        }
+        let Some(text) = race.take_text() else { return };
+        let spectating = race.spectating;
+        // the race text replaces the daily challenge too
+        if self.daily.is_some() {
+            self.toggle_daily();
+        }
+        if spectating {
+            // spectators only follow the racers' cursors over the text
+            self.set_text(&text, TextMeta::other("race"));
+            return;
+        }
+        self.go(CurrentScreen::Main);
+        self.set_text(&text, TextMeta::other("race"));
+        self.go(CurrentScreen::Editing);
+    }
+
+    /// Feeds a key of the running test to the engine. The key that ends
+    /// the test also stores the result and the replay, and in a race every
+    /// key goes out as progress.
+    pub fn press(&mut self, key: KeyInput) {
+        let Some(at_ms) = self.elapsed_ms() else { return };
+        // a key after the time is up ends the test instead of counting
+        if self.check_time() || self.engine.press(key, at_ms).is_none() {
+            return;
+        }
+        // timed off the logged key so the replay recomputes to the same wpm
+        self.wpm = Some(self.engine.wpm_at(at_ms));
+        if self.engine.finished() {
+            self.finish(at_ms);
+        } else {
+            self.send_progress(None);
+        }
+    }
+
+    /// Ends a timed test once its time is up, true if it did.
+    pub fn check_time(&mut self) -> bool {
+        let Some(limit_ms) = self.time_limit.map(|secs| secs.saturating_mul(1000)) else { return false };
+        if self.current_screen != CurrentScreen::Editing
+            || self.elapsed_ms().is_none_or(|ms| ms < limit_ms) {
+            return false;
+        }
+        self.engine.finish(limit_ms);
+        self.wpm = Some(self.engine.wpm_at(limit_ms));
+        self.finish(limit_ms);
+        true
+    }
+
+    /// Wraps up the test that ended `at_ms` in: the result, the replay and
+    /// the last word to the race.
+    fn finish(&mut self, at_ms: u64) {
+        self.go(CurrentScreen::Stats);
+        self.replays.push(Replay {
+            text: self.original_text.clone(),
+            mode: self.mode(),
+            policy: self.engine.policy(),
+            blind: self.blind,
+            geometry: self.keyboard.geometry.name().to_string(),
+            layout: self.keyboard.layout.name.clone(),
+            wpm: self.wpm.unwrap_or(0),
+            accuracy: self.engine.accuracy(),
+            mistakes: self.engine.mistakes(),
+            failed: self.engine.failed(),
+            finished_at: history::now(),
+            keystrokes: self.engine.keystrokes().to_vec(),
+            ended_ms: self.time_limit.map(|_| at_ms),
+        });
+        // same as the history, a failed write doesn't stop the session
+        let _ = self.replays.save(self.data_dir.as_deref());
+        self.send_progress(Some(at_ms));
+    }
+
+    fn send_progress(&mut self, finished_ms: Option<u64>) {
+        let engine = &self.engine;
+        let (position, wpm, accuracy) = (engine.position(), self.wpm.unwrap_or(0), engine.accuracy());
+        if let Some(race) = &mut self.race {
+            race.send_progress(position, wpm, accuracy, finished_ms);
+        }
+    }
+
+    /// Milliseconds since the test started, `None` before the first one.
+    pub fn elapsed_ms(&self) -> Option<u64> {
+        Some(self.clock.now_ms().saturating_sub(self.start_ms?))
+    }
+
+    /// Index into `original_text` the ghost has reached.
+    pub fn ghost_index(&self) -> Option<usize> {
+        let ghost = self.ghost.as_ref()?;
+        let elapsed = self.elapsed_ms()?;
+        Some(ghost.position_at(elapsed).min(self.engine.target().len().saturating_sub(1)))
+    }
+
+    /// How far this run finished ahead (negative) or behind the ghost,
+    /// `None` when it failed and didn't finish at all.
+    pub fn ghost_delta(&self) -> Option<GhostDelta> {
+        let ghost = self.ghost.as_ref()?;
+        if self.engine.failed() {
+            return None;
+        }
+        // both runs end at the limit, so what counts is how far they got
+        if let Some(secs) = self.time_limit {
+            let theirs = ghost.position_at(secs.saturating_mul(1000));
+            return Some(GhostDelta::Chars(theirs as i64 - self.engine.position() as i64));
+        }
+        let mine = self.engine.keystrokes().last()?.at_ms;
+        Some(GhostDelta::Ms(mine as i64 - ghost.duration_ms() as i64))
+    }
+
+    /// Opens the replay screen on the most recent stored run.
+    pub fn watch_last_replay(&mut self) {
+        if let Some(replay) = self.replays.replays.last() {
+            self.watch_replay(replay.clone());
+        }
+    }
+
+    /// Opens the replay screen on `replay` and checks it while at it.
+    pub fn watch_replay(&mut self, replay: Replay) {
+        if self.go(CurrentScreen::Replay) {
+            self.replay_issues = self.verify_replay(&replay);
+            self.player = Some(Player::new(replay, self.clock.now_ms()));
+        }
+    }
+
+    /// Takes in a shared replay and opens it on the replay screen. One that
+    /// checks out is kept with the other runs and its text becomes the next
+    /// test, with the replay as the ghost to race.
+    pub fn import_replay(&mut self, replay: Replay) {
+        if self.verify_replay(&replay).is_empty() {
+            self.set_text(&replay.text, TextMeta::other("replay"));
+            self.engine.set_policy(replay.policy);
+            self.time_limit = replay.ended_ms.map(|ms| ms / 1000);
+            if self.ghost_choice == GhostChoice::Off {
+                self.ghost_choice = GhostChoice::Last;
+            }
+            self.replays.push(replay.clone());
+            let _ = self.replays.save(self.data_dir.as_deref());
+        }
+        self.watch_replay(replay);
+    }
+
+    /// Recomputes the stats of `replay` from its keystrokes and lists
+    /// whatever doesn't match what the file claims.
+    pub fn verify_replay(&self, replay: &Replay) -> Vec<String> {
+        let mut issues = vec![];
+        let mut last_ms = 0;
+        for keystroke in &replay.keystrokes {
+            if keystroke.at_ms < last_ms {
+                issues.push("keystrokes out of order".to_string());
+                break;
+            }
+            last_ms = keystroke.at_ms;
+        }
+        let duration_ms = replay.duration_ms();
+        let mut engine = Engine::new(&replay.text, replay.policy);
+        // every key has to land where the file says, ghosts run on them
+        let mut mismatch = None;
+        for (i, keystroke) in replay.keystrokes.iter().enumerate().take_while(|(_, k)| k.at_ms <= duration_ms) {
+            let pressed = engine.press(keystroke.key, keystroke.at_ms);
+            if mismatch.is_none()
+                && pressed.is_none_or(|k| k.position != keystroke.position || k.correct != keystroke.correct) {
+                mismatch = Some(i + 1);
+            }
+        }
+        if let Some(i) = mismatch {
+            issues.push(format!("keystroke {i} doesn't match"));
+        }
+        let wpm = engine.wpm_at(duration_ms);
+        if wpm != replay.wpm {
+            issues.push(format!("wpm {} ≠ {wpm}", replay.wpm));
+        }
+        if engine.mistakes() != replay.mistakes {
+            issues.push(format!("mistakes {} ≠ {}", replay.mistakes, engine.mistakes()));
+        }
+        if engine.accuracy() != replay.accuracy {
+            issues.push(format!("accuracy {} ≠ {}", replay.accuracy, engine.accuracy()));
+        }
+        if engine.failed() != replay.failed {
+            issues.push("failed flag doesn't match".to_string());
+        }
+        issues
+    }
+
+    /// Writes the replay on screen, or the last one, next to where keybrah
+    /// was started.
+    pub fn export_replay(&mut self) {
+        let replay = match (&self.current_screen, &self.player) {
+            (CurrentScreen::Replay, Some(player)) => Some(&player.replay),
+            _ => self.replays.replays.last(),
+        };
+        let Some(replay) = replay else { return };
+        let path = PathBuf::from(format!("keybrah-replay-{}.json", replay.finished_at));
+        self.status = Some(match replay::export(replay, &path) {
+            Ok(()) => format!("exported to {}", path.display()),
+            Err(err) => format!("export failed: {err}"),
+        });
+    }
+
+    /// Stores the result of the test that just ended, done on entering
+    /// Stats.
+    pub fn save_result(&mut self) {
+        if self.start_ms.is_some() {
+            let result = TestResult {
+                mode: self.mode(),
+                wpm: self.wpm.unwrap_or(0),
+                accuracy: self.engine.accuracy(),
+                mistakes: self.engine.mistakes(),
+                words: self.original_text.split(' ').count(),
+                failed: self.engine.failed(),
+                finished_at: history::now(),
+            };
+            self.report = Some(Report {
+                result: result.clone(),
+                duration_ms: self.engine.ended_ms().unwrap_or(0),
+                keystrokes: self.engine.keystrokes().len(),
+                policy: self.engine.policy(),
+                blind: self.blind,
+                punctuation: self.text_options.punctuation,
+                numbers: self.text_options.numbers,
+                daily: self.daily.clone(),
+                source: self.text_meta.id.clone(),
+                source_name: self.text_meta.name.clone(),
+                language: self.text_meta.language.clone(),
+                seed: self.text_meta.seed,
+                geometry: self.keyboard.geometry.name().to_string(),
+                layout: self.keyboard.layout.name.clone(),
+                text: self.original_text.clone(),
+            });
+            // nowhere to show a write error mid-session, the run still counts
+            if self.daily.is_some() {
+                self.daily_results.push(result);
+                let _ = self.daily_results.save_daily(self.data_dir.as_deref());
+            } else {
+                self.history.push(result);
+                let _ = self.history.save(self.data_dir.as_deref());
+            }
+        }
+    }
+
+    /// What made the current text besides its source and seed, everything
+    /// that has to match for the seed to give the same text again.
+    pub fn text_settings(&self) -> String {
+        let mut settings = vec![match self.time_limit {
+            Some(secs) => format!("time {secs}"),
+            None => format!("words {}", self.engine.word_count()),
+        }];
+        settings.extend(self.text_options.name().map(String::from));
+        settings.extend(self.text_options.rates_name());
+        settings.join(" · ")
+    }
+
+    /// Results are compared within the same text length and error policy,
+    /// daily challenges within the same day.
+    pub fn mode(&self) -> String {
+        if let Some(date) = &self.daily {
+            return self.daily_mode(date);
+        }
+        let length = match self.time_limit {
+            Some(secs) => format!("time {secs}"),
+            None => format!("words {}", self.engine.word_count()),
+        };
+        let mut mode = format!("{length} · {}", self.engine.policy().name());
+        // texts of other sources aren't compared with the default word list
+        if self.text_meta.id != DEFAULT_SOURCE && self.sources.get(&self.text_meta.id).is_some() {
+            mode = format!("{mode} · {}", self.text_meta.name);
+        }
+        for options in [self.text_options.name().map(String::from), self.text_options.rates_name()].into_iter().flatten() {
+            mode = format!("{mode} · {options}");
+        }
+        mode
+    }
+
+    /// The speed the pace caret runs at, `None` when it is off or there is
+    /// no result to pace against yet.
+    pub fn pace_wpm(&self) -> Option<u16> {
+        match self.pace {
+            Pace::Off => None,
+            Pace::Wpm(wpm) => Some(wpm),
+            Pace::Average => self.results().average(&self.mode()),
+            Pace::Best => self.results().best(&self.mode()),
+        }
+    }
+
+    /// Index into `original_text` the pace caret has reached.
+    pub fn pace_index(&self) -> Option<usize> {
+        let wpm = self.pace_wpm()?;
+        let elapsed = self.elapsed_ms()? as f64 / 1000.0;
+        let index = (wpm as f64 * 5.0 / 60.0 * elapsed) as usize;
+        Some(index.min(self.engine.target().len().saturating_sub(1)))
+    }
+
+    /// Puts the test back to untyped, done on entering Main.
     pub fn reset(&mut self) {
-        self.wrong = false;
-        self.mistakes = 0;
-        self.accuracy= 100;
-        self.current_screen = CurrentScreen::Main;
         self.currently_editing = None;
-        self.edit_text(&self.original_text.clone());
-        self.lefts[self.key_input.len()] = ' ';
-        self.rights[self.key_input.len()] = ' ';
-        self.key_input.clear();
+        self.engine.reset();
         self.wpm = Some(0);
     }
 
-    pub fn add_map(&mut self) {
-        self.left_finger_map.insert('a', '5');
-        self.left_finger_map.insert('q', '5');
-        self.left_finger_map.insert('z', '5');
-        self.left_finger_map.insert('w', '4');
-        self.left_finger_map.insert('s', '4');
-        self.left_finger_map.insert('x', '4');
-        self.left_finger_map.insert('e', '3');
-        self.left_finger_map.insert('d', '3');
-        self.left_finger_map.insert('c', '3');
-        self.left_finger_map.insert('r', '2');
-        self.left_finger_map.insert('f', '2');
-        self.left_finger_map.insert('v', '2');
-        self.left_finger_map.insert('t', '2');
-        self.left_finger_map.insert('g', '2');
-        self.left_finger_map.insert('b', '2');
-        self.left_finger_map.insert(' ', ' ');
-        self.right_finger_map.insert('y', '2');
-        self.right_finger_map.insert('h', '2');
-        self.right_finger_map.insert('n', '2');
-        self.right_finger_map.insert('u', '2');
-        self.right_finger_map.insert('j', '2');
-        self.right_finger_map.insert('m', '2');
-        self.right_finger_map.insert('i', '3');
-        self.right_finger_map.insert('k', '3');
-        self.right_finger_map.insert(',', '3');
-        self.right_finger_map.insert('o', '4');
-        self.right_finger_map.insert('l', '4');
-        self.right_finger_map.insert('.', '4');
-        self.right_finger_map.insert('p', '5');
-        self.right_finger_map.insert(';', '5');
-        self.right_finger_map.insert('/', '5');
-        let chars: Vec<char> = self.original_text.chars().collect();
-        let mut right_nums = vec![];
-        let mut left_nums = vec![];
-        for c in chars {
-            if self.right_finger_map.contains_key(&c) {
-                right_nums.push(self.right_finger_map[&c]);
-                left_nums.push(' ');
-                self.rights.push(' ');
-                self.lefts.push(' ');
-            }
-            if self.left_finger_map.contains_key(&c) {
-                left_nums.push(self.left_finger_map[&c]);
-                right_nums.push(' ');
-                self.rights.push(' ');
-                self.lefts.push(' ');
-            } 
+    /// Switches to an imported layout, keeping the geometry unless the
+    /// import says which board it is for.
+    pub fn set_layout(&mut self, geometry: Option<Geometry>, layout: KeyLayout) {
+        let geometry = geometry.unwrap_or(self.keyboard.geometry);
+        self.keyboard = Keyboard::new(geometry, layout);
+    }
+
+    pub fn next_geometry(&mut self) {
+        let geometry = self.keyboard.geometry.next();
+        self.keyboard = Keyboard::new(geometry, self.keyboard.layout.clone());
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use keybrah::clock::ManualClock;
+
+    use super::*;
+
+    /// An app on `text` whose clock only moves by hand, stored nowhere.
+    fn app(text: &str) -> (App, ManualClock) {
+        let clock = ManualClock::new(1000);
+        let mut app = App::new();
+        app.clock = Box::new(clock.clone());
+        app.data_dir = None;
+        app.set_text(text, TextMeta::other("test"));
+        (app, clock)
+    }
+
+    fn type_keys(app: &mut App, keys: &str) {
+        for ch in keys.chars() {
+            app.press(KeyInput::Char(ch));
         }
-        self.right_nums= right_nums;
-        self.left_nums= left_nums;
     }
 
+    #[test]
+    fn press_times_keys_by_the_clock() {
+        let (mut app, clock) = app("hello world");
+        assert_eq!(app.elapsed_ms(), None);
+        app.go(CurrentScreen::Editing);
+        clock.advance(6000);
+        assert_eq!(app.elapsed_ms(), Some(6000));
+        type_keys(&mut app, "hello worl");
+        // 10 chars, 2 words in 6s
+        assert_eq!(app.wpm, Some(20));
+        assert_eq!(app.engine.keystrokes().last().map(|k| k.at_ms), Some(6000));
+        clock.advance(6000);
+        type_keys(&mut app, "d");
+        assert_eq!(app.wpm, Some(11));
+        assert_eq!(app.current_screen, CurrentScreen::Stats);
+        assert_eq!(app.replays.replays.len(), 1);
+    }
+
+    #[test]
+    fn check_time_ends_at_the_limit() {
+        let (mut app, clock) = app("hello world");
+        app.time_limit = Some(10);
+        app.go(CurrentScreen::Editing);
+        clock.advance(2000);
+        type_keys(&mut app, "hello");
+        clock.advance(7999);
+        assert!(!app.check_time());
+        clock.advance(1);
+        assert!(app.check_time());
+        // scored at the limit, however late the check came
+        assert_eq!(app.engine.ended_ms(), Some(10_000));
+        assert_eq!(app.wpm, Some(6));
+        assert_eq!(app.current_screen, CurrentScreen::Stats);
+        assert_eq!(app.replays.replays[0].ended_ms, Some(10_000));
+    }
+
+    #[test]
+    fn daily_is_never_timed() {
+        let (mut app, _) = app("hello world");
+        app.time_limit = Some(15);
+        app.toggle_daily();
+        assert_eq!(app.time_limit, None);
+        assert!(app.mode().starts_with("daily "));
+        app.toggle_daily();
+        assert_eq!(app.time_limit, Some(15));
+        assert_eq!(app.original_text, "hello world");
+    }
+
+    #[test]
+    fn a_key_after_the_limit_ends_the_test() {
+        let (mut app, clock) = app("hello world");
+        app.time_limit = Some(10);
+        app.go(CurrentScreen::Editing);
+        clock.advance(2000);
+        type_keys(&mut app, "hello");
+        clock.advance(12_000);
+        type_keys(&mut app, " ");
+        assert_eq!(app.engine.position(), 5);
+        assert_eq!(app.engine.ended_ms(), Some(10_000));
+        assert_eq!(app.current_screen, CurrentScreen::Stats);
+    }
 }
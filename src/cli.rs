@@ -0,0 +1,231 @@
+// Command-line arguments. All of them are optional, plain `keybrah` opens
+// the usual words test. The KEYBRAH_* environment variables still work,
+// arguments win where both are given.
+
+use std::path::PathBuf;
+
+use crate::{app::TEST_WORDS, net::DEFAULT_PORT, words::TextOptions};
+
+pub const USAGE: &str = "\
+usage: keybrah [COMMAND] [OPTIONS]
+
+commands:
+  words [N]        a test of N words (default 10, at most 10000)
+  time [SECONDS]   type for SECONDS seconds (default 60, at most 3600)
+  file PATH        type the text of a file
+  replay PATH      watch a shared replay file
+  race host [PORT] host a LAN race (default port 7878)
+  race join ADDR   join the race hosted at ADDR, e.g. 192.168.1.5:7878
+  race watch ADDR  watch the race hosted at ADDR
+  stats            print the stored results and exit
+
+options:
+  --seed N         generate the text from seed N
+  --lang NAME      text source by id or language, e.g. english or en
+  --layout NAME    qwerty, colemak, dvorak or a QMK/XKB layout file
+  --plugin PATH    add a text source plugin, can be given more than once
+  --name NAME      your name in a race (default $USER)
+  --punctuation    capitalized sentences with punctuation
+  --numbers        numbers between the words
+  --rates LIST     percent of words that get each, e.g. comma=20,number=5
+                   (comma, period, quote, paren, number)
+  --json           print the result as JSON on exit
+  -h, --help       show this help
+
+environment, used when the command or option isn't given:
+  KEYBRAH_HOST=PORT      same as race host (empty for 7878)
+  KEYBRAH_JOIN=ADDRESS   same as race join
+  KEYBRAH_WATCH=ADDRESS  same as race watch
+  KEYBRAH_REPLAY=PATH    same as replay
+  KEYBRAH_NAME=NAME      same as --name
+  KEYBRAH_PLUGINS=PATHS  same as --plugin, separated like PATH
+  KEYBRAH_SOURCE=NAME    same as --lang
+  KEYBRAH_LAYOUT=NAME    same as --layout
+  KEYBRAH_DATA_DIR=DIR   where results and replays are kept
+                         (default $XDG_DATA_HOME/keybrah)
+";
+
+pub const DEFAULT_TIME_SECS: u64 = 60;
+
+/// Longest test `words` can ask for.
+pub const MAX_WORDS: u64 = 10_000;
+/// Longest test `time` can ask for, an hour.
+pub const MAX_TIME_SECS: u64 = 3600;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    Words(usize),
+    Time(u64),
+    File(PathBuf),
+    Replay(PathBuf),
+    Race(RaceRole),
+    Stats,
+}
+
+/// Which side of a LAN race to take.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RaceRole {
+    Host(u16),
+    Join(String),
+    Watch(String),
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Args {
+    pub command: Option<Command>,
+    pub seed: Option<u64>,
+    pub lang: Option<String>,
+    pub layout: Option<String>,
+    pub plugins: Vec<PathBuf>,
+    pub name: Option<String>,
+    pub options: TextOptions,
+    pub json: bool,
+    pub help: bool,
+}
+
+/// Reads the arguments after the program name.
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Args, String> {
+    let mut parsed = Args::default();
+    let mut args = args.into_iter().peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => parsed.help = true,
+            "--json" => parsed.json = true,
+            "--seed" => parsed.seed = Some(number(&arg, args.next())?),
+            "--lang" => parsed.lang = Some(value(&arg, args.next())?),
+            "--layout" => parsed.layout = Some(value(&arg, args.next())?),
+            "--plugin" => parsed.plugins.push(value(&arg, args.next())?.into()),
+            "--name" => parsed.name = Some(value(&arg, args.next())?),
+            "--punctuation" => parsed.options.punctuation = true,
+            "--numbers" => parsed.options.numbers = true,
+            "--rates" => rates(&mut parsed.options, &value(&arg, args.next())?)?,
+            _ if arg.starts_with('-') => return Err(format!("unknown option {arg}")),
+            _ if parsed.command.is_some() => return Err(format!("unexpected argument {arg}")),
+            "words" | "time" => {
+                // the count is optional, the next argument may be an option
+                let count = args.next_if(|next| next.parse::<u64>().is_ok())
+                    .map(|next| number(&arg, Some(next)))
+                    .transpose()?;
+                let max = if arg == "words" { MAX_WORDS } else { MAX_TIME_SECS };
+                if count == Some(0) {
+                    return Err(format!("{arg} needs more than 0"));
+                }
+                if count.is_some_and(|count| count > max) {
+                    return Err(format!("{arg} goes up to {max}"));
+                }
+                parsed.command = Some(match arg.as_str() {
+                    "words" => Command::Words(count.map(|n| n as usize).unwrap_or(TEST_WORDS)),
+                    _ => Command::Time(count.unwrap_or(DEFAULT_TIME_SECS)),
+                });
+            }
+            "file" => parsed.command = Some(Command::File(value(&arg, args.next())?.into())),
+            "replay" => parsed.command = Some(Command::Replay(value(&arg, args.next())?.into())),
+            "race" => {
+                let role = value(&arg, args.next())?;
+                let role = match role.as_str() {
+                    // the port is optional like the count of `words`
+                    "host" => RaceRole::Host(args.next_if(|next| !next.starts_with('-'))
+                        .map(|port| port.parse().map_err(|_| format!("race host needs a port, not {port}")))
+                        .transpose()?
+                        .unwrap_or(DEFAULT_PORT)),
+                    "join" => RaceRole::Join(value("race join", args.next())?),
+                    "watch" => RaceRole::Watch(value("race watch", args.next())?),
+                    _ => return Err(format!("race is host, join or watch, not {role}")),
+                };
+                parsed.command = Some(Command::Race(role));
+            }
+            "stats" => parsed.command = Some(Command::Stats),
+            _ => return Err(format!("unknown command {arg}")),
+        }
+    }
+    Ok(parsed)
+}
+
+/// Reads `name=percent` pairs into the rates of `options`.
+fn rates(options: &mut TextOptions, list: &str) -> Result<(), String> {
+    for pair in list.split(',') {
+        let (name, percent) = pair.split_once('=')
+            .ok_or_else(|| format!("--rates needs name=percent, not {pair}"))?;
+        let rate = match name {
+            "comma" => &mut options.comma_rate,
+            "period" => &mut options.period_rate,
+            "quote" => &mut options.quote_rate,
+            "paren" => &mut options.paren_rate,
+            "number" => &mut options.number_rate,
+            _ => return Err(format!("unknown rate {name}")),
+        };
+        *rate = percent.parse().ok().filter(|p| *p <= 100)
+            .ok_or_else(|| format!("{name} needs a percent, not {percent}"))?;
+    }
+    Ok(())
+}
+
+fn value(arg: &str, next: Option<String>) -> Result<String, String> {
+    next.ok_or_else(|| format!("{arg} needs a value"))
+}
+
+fn number(arg: &str, next: Option<String>) -> Result<u64, String> {
+    let value = value(arg, next)?;
+    value.parse().map_err(|_| format!("{arg} needs a number, not {value}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(line: &str) -> Result<Args, String> {
+        parse(line.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn commands_and_options() {
+        let args = parse_str("words 50 --lang en --seed 7 --json").unwrap();
+        assert_eq!(args.command, Some(Command::Words(50)));
+        assert_eq!(args.lang.as_deref(), Some("en"));
+        assert_eq!(args.seed, Some(7));
+        assert!(args.json);
+        assert_eq!(parse_str("time --layout colemak").unwrap().command, Some(Command::Time(60)));
+        assert_eq!(parse_str("file notes.txt").unwrap().command, Some(Command::File("notes.txt".into())));
+        assert_eq!(parse_str("").unwrap(), Args::default());
+        let options = parse_str("--numbers --rates comma=20,number=0").unwrap().options;
+        assert!(options.numbers && !options.punctuation);
+        assert_eq!((options.comma_rate, options.number_rate), (20, 0));
+        assert_eq!(options.period_rate, TextOptions::default().period_rate);
+    }
+
+    #[test]
+    fn races_and_replays() {
+        assert_eq!(parse_str("race host").unwrap().command, Some(Command::Race(RaceRole::Host(DEFAULT_PORT))));
+        let args = parse_str("race host 9000 --name ada").unwrap();
+        assert_eq!(args.command, Some(Command::Race(RaceRole::Host(9000))));
+        assert_eq!(args.name.as_deref(), Some("ada"));
+        assert_eq!(parse_str("race join 10.0.0.2:7878").unwrap().command,
+            Some(Command::Race(RaceRole::Join("10.0.0.2:7878".to_string()))));
+        assert_eq!(parse_str("race watch host:1").unwrap().command,
+            Some(Command::Race(RaceRole::Watch("host:1".to_string()))));
+        assert_eq!(parse_str("replay run.json").unwrap().command, Some(Command::Replay("run.json".into())));
+        let plugins = parse_str("--plugin a --plugin b/c").unwrap().plugins;
+        assert_eq!(plugins, [PathBuf::from("a"), PathBuf::from("b/c")]);
+        assert!(parse_str("race").is_err());
+        assert!(parse_str("race join").is_err());
+        assert!(parse_str("race lead x").is_err());
+        assert!(parse_str("race host 70000").is_err());
+        assert!(parse_str("replay").is_err());
+    }
+
+    #[test]
+    fn bad_arguments() {
+        assert!(parse_str("time 0").is_err());
+        assert!(parse_str("time 18446744073709551615").is_err());
+        assert!(parse_str("words 4000000000").is_err());
+        assert_eq!(parse_str("words 10000").unwrap().command, Some(Command::Words(10_000)));
+        assert_eq!(parse_str("time 3600").unwrap().command, Some(Command::Time(3600)));
+        assert!(parse_str("words 5 time 6").is_err());
+        assert!(parse_str("--seed").is_err());
+        assert!(parse_str("--colour").is_err());
+        assert!(parse_str("lessons").is_err());
+        assert!(parse_str("--rates comma=101").is_err());
+        assert!(parse_str("--rates commas=5").is_err());
+        assert!(parse_str("--rates comma").is_err());
+    }
+}
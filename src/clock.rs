@@ -0,0 +1,68 @@
+// Where the time of a test comes from. The engine only ever sees
+// millisecond timestamps, a clock hands them out so tests can drive time
+// by hand.
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+pub trait Clock {
+    /// Milliseconds since a fixed point of this clock, only differences
+    /// between two readings mean anything.
+    fn now_ms(&self) -> u64;
+}
+
+/// Wall time, counted from when the clock was made.
+#[derive(Clone, Copy, Debug)]
+pub struct SystemClock {
+    origin: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> SystemClock {
+        SystemClock { origin: Instant::now() }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> SystemClock {
+        SystemClock::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now_ms(&self) -> u64 {
+        self.origin.elapsed().as_millis() as u64
+    }
+}
+
+/// A clock that only moves when told to. Clones share the same time, so
+/// one can be handed over and the other kept to turn it.
+#[derive(Clone, Debug, Default)]
+pub struct ManualClock {
+    now: Arc<AtomicU64>,
+}
+
+impl ManualClock {
+    pub fn new(now_ms: u64) -> ManualClock {
+        ManualClock { now: Arc::new(AtomicU64::new(now_ms)) }
+    }
+
+    pub fn set(&self, now_ms: u64) {
+        self.now.store(now_ms, Ordering::Relaxed);
+    }
+
+    pub fn advance(&self, ms: u64) {
+        self.now.fetch_add(ms, Ordering::Relaxed);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now_ms(&self) -> u64 {
+        self.now.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,557 @@
+// The typing test itself: a target text, the keys typed at it and what
+// they add up to. Knows nothing about terminals, keys come in with their
+// timestamps and the state is read back off the `Engine`.
+
+use std::{collections::BTreeMap, iter, ops::Range};
+
+use serde::{Deserialize, Serialize};
+
+/// What happens when a wrong key is pressed.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum ErrorPolicy {
+    /// The cursor doesn't move until the right key is pressed.
+    #[default]
+    StopOnLetter,
+    /// Wrong keys are typed like any other and fixed with Backspace.
+    FreeCorrection,
+    /// Like free correction, but space is refused while the word has errors.
+    StopOnWord,
+    /// Like free correction without Backspace, errors stay where they are.
+    Confidence,
+    /// The test fails on the first wrong key.
+    SuddenDeath,
+}
+
+impl ErrorPolicy {
+    pub const ALL: [ErrorPolicy; 5] = [
+        ErrorPolicy::StopOnLetter,
+        ErrorPolicy::FreeCorrection,
+        ErrorPolicy::StopOnWord,
+        ErrorPolicy::Confidence,
+        ErrorPolicy::SuddenDeath,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorPolicy::StopOnLetter => "stop letter",
+            ErrorPolicy::FreeCorrection => "free",
+            ErrorPolicy::StopOnWord => "stop word",
+            ErrorPolicy::Confidence => "confidence",
+            ErrorPolicy::SuddenDeath => "sudden death",
+        }
+    }
+
+    pub fn next(&self) -> ErrorPolicy {
+        let i = ErrorPolicy::ALL.iter().position(|p| p == self).unwrap_or(0);
+        ErrorPolicy::ALL[(i + 1) % ErrorPolicy::ALL.len()]
+    }
+
+    /// Whether wrong chars get typed into the text instead of holding the
+    /// cursor back.
+    pub fn free_typing(&self) -> bool {
+        *self != ErrorPolicy::StopOnLetter
+    }
+
+    pub fn allows_backspace(&self) -> bool {
+        self.free_typing() && *self != ErrorPolicy::Confidence
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeyInput {
+    Char(char),
+    Backspace,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Keystroke {
+    /// Milliseconds since the test started.
+    pub at_ms: u64,
+    pub key: KeyInput,
+    /// Index into the text the cursor was at after this key.
+    pub position: usize,
+    pub correct: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CellState {
+    Correct,
+    Wrong,
+    /// Typed past the end of a word.
+    Extra,
+    Pending,
+}
+
+/// One displayed char of the text. `target` is the index into the text it
+/// stands for, extras typed past the end of a word have none. `typed` is
+/// the key that was pressed there, if any.
+#[derive(Clone, Copy, Debug)]
+pub struct Cell {
+    pub ch: char,
+    pub typed: Option<char>,
+    pub state: CellState,
+    pub target: Option<usize>,
+}
+
+/// A test of one text under one error policy. Every key costs about the
+/// length of a word, never the length of the text.
+#[derive(Clone, Debug)]
+pub struct Engine {
+    policy: ErrorPolicy,
+    /// The text as chars, fixed for the length of a test.
+    target: Vec<char>,
+    /// The state of every char of `target`. Updated in place as keys come in.
+    cells: Vec<Cell>,
+    /// Extras free typing put past the end of a word, by the index of the
+    /// char they stand before (the space after the word, or the end).
+    extras: BTreeMap<usize, Vec<char>>,
+    /// Index into the text of the next char to type, after any extras
+    /// standing before it.
+    position: usize,
+    // running counts, so reading them doesn't walk the text
+    correct: usize,
+    words: usize,
+    word_count: usize,
+    wrong: bool,
+    mistakes: u16,
+    accuracy: u16,
+    // set when sudden death ends the test early
+    failed: bool,
+    finished: bool,
+    /// When the test ended, on its last key or when its time was up.
+    ended_ms: Option<u64>,
+    // first wrong key pressed at each index in stop on letter
+    wrong_keys: Vec<(usize, char)>,
+    /// Every key of the test, what a replay is made of.
+    keystrokes: Vec<Keystroke>,
+}
+
+impl Engine {
+    pub fn new(text: &str, policy: ErrorPolicy) -> Engine {
+        let mut engine = Engine {
+            policy,
+            target: text.chars().collect(),
+            cells: vec![],
+            extras: BTreeMap::new(),
+            position: 0,
+            correct: 0,
+            words: 0,
+            word_count: text.split(' ').count(),
+            wrong: false,
+            mistakes: 0,
+            accuracy: 100,
+            failed: false,
+            finished: false,
+            ended_ms: None,
+            wrong_keys: vec![],
+            keystrokes: vec![],
+        };
+        engine.reset();
+        engine
+    }
+
+    /// Puts the test back to nothing typed.
+    pub fn reset(&mut self) {
+        self.cells = self.target.iter().enumerate()
+            .map(|(i, ch)| Cell { ch: *ch, typed: None, state: CellState::Pending, target: Some(i) })
+            .collect();
+        self.extras.clear();
+        self.position = 0;
+        self.correct = 0;
+        self.words = 0;
+        self.wrong = false;
+        self.mistakes = 0;
+        self.accuracy = 100;
+        self.failed = false;
+        self.finished = false;
+        self.ended_ms = None;
+        self.wrong_keys.clear();
+        self.keystrokes.clear();
+    }
+
+    pub fn policy(&self) -> ErrorPolicy {
+        self.policy
+    }
+
+    /// Switches to `policy`, starting the test over.
+    pub fn set_policy(&mut self, policy: ErrorPolicy) {
+        self.policy = policy;
+        self.reset();
+    }
+
+    pub fn target(&self) -> &[char] {
+        &self.target
+    }
+
+    /// One cell per char of the text, extras aside.
+    pub fn cells(&self) -> &[Cell] {
+        &self.cells
+    }
+
+    /// The extras typed before the char at `index`.
+    pub fn extras(&self, index: usize) -> &[char] {
+        self.extras.get(&index).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// The cells of `range` of the text as they show, each after the
+    /// extras typed before it. Extras past the end of the text come with
+    /// a range that reaches it.
+    pub fn shown(&self, range: Range<usize>) -> impl Iterator<Item = Cell> + '_ {
+        let end = range.end.min(self.target.len());
+        let extras = move |index: usize| self.extras(index).iter().map(|ch| {
+            Cell { ch: *ch, typed: Some(*ch), state: CellState::Extra, target: None }
+        });
+        let last = (end == self.target.len()).then_some(end);
+        (range.start.min(end)..end)
+            .flat_map(move |index| extras(index).chain(iter::once(self.cells[index])))
+            .chain(last.into_iter().flat_map(extras))
+    }
+
+    /// Whether the last key was wrong and is holding the cursor back.
+    pub fn wrong(&self) -> bool {
+        self.wrong
+    }
+
+    pub fn mistakes(&self) -> u16 {
+        self.mistakes
+    }
+
+    pub fn accuracy(&self) -> u16 {
+        self.accuracy
+    }
+
+    /// Whether sudden death ended the test early.
+    pub fn failed(&self) -> bool {
+        self.failed
+    }
+
+    pub fn finished(&self) -> bool {
+        self.finished
+    }
+
+    /// When the test ended, on its last key or when its time was up.
+    pub fn ended_ms(&self) -> Option<u64> {
+        self.ended_ms
+    }
+
+    /// First wrong key pressed at each index in stop on letter.
+    pub fn wrong_keys(&self) -> &[(usize, char)] {
+        &self.wrong_keys
+    }
+
+    /// Every key of the test, what a replay is made of.
+    pub fn keystrokes(&self) -> &[Keystroke] {
+        &self.keystrokes
+    }
+
+    /// Feeds `key`, pressed `at_ms` into the test, and logs it. Keys after
+    /// the end are ignored.
+    pub fn press(&mut self, key: KeyInput, at_ms: u64) -> Option<&Keystroke> {
+        if self.finished {
+            return None;
+        }
+        let mistakes = self.mistakes;
+        match key {
+            KeyInput::Char(value) if self.policy.free_typing() => self.type_free(value),
+            KeyInput::Char(value) => self.type_strict(value),
+            KeyInput::Backspace => self.backspace(),
+        }
+        if self.finished {
+            self.ended_ms = Some(at_ms);
+        }
+        self.keystrokes.push(Keystroke {
+            at_ms,
+            key,
+            position: self.position,
+            correct: self.mistakes == mistakes && !self.wrong,
+        });
+        self.keystrokes.last()
+    }
+
+    /// Ends the test where it is `at_ms` in, e.g. when its time is up.
+    pub fn finish(&mut self, at_ms: u64) {
+        self.finished = true;
+        self.ended_ms = Some(at_ms);
+    }
+
+    /// Index into the text the cursor is at.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// The char the cursor is waiting for.
+    pub fn next_char(&self) -> Option<char> {
+        self.target.get(self.position).copied()
+    }
+
+    /// Words finished so far.
+    pub fn words_typed(&self) -> usize {
+        self.words
+    }
+
+    /// Words in the whole text.
+    pub fn word_count(&self) -> usize {
+        self.word_count
+    }
+
+    /// WPM of what is typed so far if it took `at_ms`.
+    pub fn wpm_at(&self, at_ms: u64) -> u16 {
+        if at_ms == 0 {
+            return 0;
+        }
+        let numinator = match self.policy {
+            ErrorPolicy::StopOnLetter => {
+                let cnums: f64 = self.position as f64;
+                (cnums - (self.mistakes as f64)) * 60.0
+            }
+            // a failed run scores nothing
+            ErrorPolicy::SuddenDeath if self.failed => 0.0,
+            // only chars that are still right count, fixing errors costs time
+            // and errors left behind in confidence mode cost the chars
+            _ => self.correct as f64 * 60.0,
+        };
+        (numinator / (5.0 * at_ms as f64 / 1000.0)) as u16
+    }
+
+    fn update_accuracy(&mut self) {
+        let ori_len = self.target.len() as f32;
+        let correct = ori_len - (self.mistakes as f32);
+        self.accuracy = ((correct / ori_len) * 100.0) as u16;
+    }
+
+    /// Sets the cell at `index`, keeping the count of right chars.
+    fn set_cell(&mut self, index: usize, typed: Option<char>, state: CellState) {
+        let cell = &mut self.cells[index];
+        if cell.state == CellState::Correct {
+            self.correct -= 1;
+        }
+        if state == CellState::Correct {
+            self.correct += 1;
+        }
+        cell.typed = typed;
+        cell.state = state;
+    }
+
+    /// Takes back the last typed char when the policy allows it. Taking
+    /// back a space also gives back the chars its word skipped.
+    fn backspace(&mut self) {
+        if !self.policy.allows_backspace() {
+            return;
+        }
+        self.wrong = false;
+        if let Some(extras) = self.extras.get_mut(&self.position) {
+            extras.pop();
+            if extras.is_empty() {
+                self.extras.remove(&self.position);
+            }
+            return;
+        }
+        if self.position == 0 {
+            return;
+        }
+        self.position -= 1;
+        self.set_cell(self.position, None, CellState::Pending);
+        if self.target[self.position] == ' ' {
+            self.words -= 1;
+            while self.position > 0 && self.cells[self.position - 1].typed.is_none()
+                && self.cells[self.position - 1].state == CellState::Wrong {
+                self.position -= 1;
+                self.set_cell(self.position, None, CellState::Pending);
+            }
+        }
+    }
+
+    /// Types `value` for stop on letter, the cursor only moves on the
+    /// right key and a run of wrong keys is one mistake.
+    fn type_strict(&mut self, value: char) {
+        let Some(ch) = self.next_char() else { return };
+        if ch == value {
+            self.set_cell(self.position, Some(value), CellState::Correct);
+            if ch == ' ' {
+                self.words += 1;
+            }
+            self.position += 1;
+            self.wrong = false;
+            self.finished = self.position == self.target.len();
+        } else if !self.wrong {
+            self.wrong = true;
+            self.wrong_keys.push((self.position, value));
+            self.mistakes = self.mistakes.saturating_add(1);
+            self.update_accuracy();
+        }
+    }
+
+    /// Types `value` for the free-typing policies. Space moves on to the
+    /// next word and leaves its untyped chars wrong, anything else is kept
+    /// even if it is wrong, past the end of a word as an extra.
+    fn type_free(&mut self, value: char) {
+        // the space that ends the current word, none in the last word
+        let space = self.target[self.position..].iter()
+            .position(|c| *c == ' ')
+            .map(|i| self.position + i);
+        if value == ' ' {
+            // no empty words and nothing past the last word
+            let Some(space) = space else { return };
+            let after_word = !self.extras(self.position).is_empty()
+                || self.position > 0 && self.target[self.position - 1] != ' ';
+            if !after_word {
+                return;
+            }
+            if self.policy == ErrorPolicy::StopOnWord && !self.word_correct(space) {
+                self.wrong = true;
+                return;
+            }
+        }
+        self.wrong = false;
+        let expected = self.next_char().filter(|ch| !(value != ' ' && *ch == ' '));
+        if expected != Some(value) {
+            self.mistakes = self.mistakes.saturating_add(1);
+            self.update_accuracy();
+            if self.policy == ErrorPolicy::SuddenDeath {
+                self.failed = true;
+                self.finished = true;
+                return;
+            }
+        }
+        match (value, space) {
+            (' ', Some(space)) => {
+                for index in self.position..space {
+                    self.set_cell(index, None, CellState::Wrong);
+                }
+                self.set_cell(space, Some(' '), CellState::Correct);
+                self.words += 1;
+                self.position = space + 1;
+            }
+            _ if expected.is_some() || self.next_char().is_some_and(|ch| ch != ' ') => {
+                let state = if self.target[self.position] == value { CellState::Correct } else { CellState::Wrong };
+                self.set_cell(self.position, Some(value), state);
+                self.position += 1;
+            }
+            _ => self.extras.entry(self.position).or_default().push(value),
+        }
+
+        self.finished = self.position == self.target.len() && match self.policy {
+            // the last word has to be right before the test can end
+            ErrorPolicy::StopOnWord => self.word_correct(self.position),
+            _ => true,
+        };
+    }
+
+    /// Whether the word ending before index `end` was typed exactly,
+    /// nothing missing, wrong or extra.
+    fn word_correct(&self, end: usize) -> bool {
+        let start = self.target[..end].iter()
+            .rposition(|c| *c == ' ')
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        self.extras(end).is_empty()
+            && self.cells[start..end].iter().all(|c| c.state == CellState::Correct)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn typed(text: &str, policy: ErrorPolicy, keys: &str) -> Engine {
+        let mut engine = Engine::new(text, policy);
+        for (i, key) in keys.chars().enumerate() {
+            let key = if key == '<' { KeyInput::Backspace } else { KeyInput::Char(key) };
+            engine.press(key, (i as u64 + 1) * 100);
+        }
+        engine
+    }
+
+    fn states(engine: &Engine) -> Vec<CellState> {
+        engine.cells().iter().map(|c| c.state).collect()
+    }
+
+    #[test]
+    fn stop_on_letter() {
+        let engine = typed("ab cd", ErrorPolicy::StopOnLetter, "axxb");
+        assert_eq!(engine.position(), 2);
+        // a run of wrong keys is one mistake
+        assert_eq!(engine.mistakes(), 1);
+        assert_eq!(engine.wrong_keys(), &[(1, 'x')]);
+        assert!(typed("ab cd", ErrorPolicy::StopOnLetter, "ax").wrong());
+        assert!(typed("ab cd", ErrorPolicy::StopOnLetter, "ab cd").finished());
+    }
+
+    #[test]
+    fn backspace_across_a_space() {
+        use CellState::*;
+        let engine = typed("ab cd", ErrorPolicy::FreeCorrection, "a ");
+        assert_eq!(engine.position(), 3);
+        assert_eq!(engine.words_typed(), 1);
+        assert_eq!(states(&engine), [Correct, Wrong, Correct, Pending, Pending]);
+        // taking back the space gives back the skipped char too
+        let engine = typed("ab cd", ErrorPolicy::FreeCorrection, "a <");
+        assert_eq!(engine.position(), 1);
+        assert_eq!(engine.words_typed(), 0);
+        assert_eq!(states(&engine), [Correct, Pending, Pending, Pending, Pending]);
+    }
+
+    #[test]
+    fn stop_on_word_refuses_the_space() {
+        let engine = typed("ab cd", ErrorPolicy::StopOnWord, "ax ");
+        assert_eq!(engine.position(), 2);
+        assert!(engine.wrong());
+        let engine = typed("ab cd", ErrorPolicy::StopOnWord, "ax <b ");
+        assert_eq!(engine.position(), 3);
+        // the last word has to be right as well
+        assert!(!typed("ab cd", ErrorPolicy::StopOnWord, "ab cx").finished());
+        assert!(typed("ab cd", ErrorPolicy::StopOnWord, "ab cx<d").finished());
+    }
+
+    #[test]
+    fn extra_characters() {
+        let engine = typed("ab cd", ErrorPolicy::FreeCorrection, "abxy");
+        assert_eq!(engine.position(), 2);
+        assert_eq!(engine.mistakes(), 2);
+        assert_eq!(engine.extras(2), ['x', 'y']);
+        let shown: String = engine.shown(0..5).map(|c| c.ch).collect();
+        assert_eq!(shown, "abxy cd");
+        // extras come off before the chars of the word
+        let engine = typed("ab cd", ErrorPolicy::FreeCorrection, "abxy <<");
+        assert_eq!(engine.position(), 2);
+        assert_eq!(engine.extras(2), ['x']);
+        // and past the last word until it is right
+        let engine = typed("ab cd", ErrorPolicy::StopOnWord, "ab cxz");
+        assert!(!engine.finished());
+        let shown: String = engine.shown(3..5).map(|c| c.ch).collect();
+        assert_eq!(shown, "cdz");
+        assert!(typed("ab cd", ErrorPolicy::StopOnWord, "ab cxz<<d").finished());
+        // only the 5 right chars count towards wpm
+        assert_eq!(typed("ab cd", ErrorPolicy::FreeCorrection, "abxy cd").wpm_at(12_000), 5);
+    }
+
+    #[test]
+    fn confidence_ignores_backspace() {
+        let engine = typed("ab cd", ErrorPolicy::Confidence, "ax<");
+        assert_eq!(engine.position(), 2);
+        assert_eq!(engine.cells()[1].state, CellState::Wrong);
+        assert_eq!(engine.mistakes(), 1);
+        assert_eq!(engine.keystrokes()[2].key, KeyInput::Backspace);
+    }
+
+    #[test]
+    fn sudden_death_scores_nothing() {
+        let mut engine = typed("ab cd", ErrorPolicy::SuddenDeath, "ax");
+        assert!(engine.failed());
+        assert!(engine.finished());
+        assert_eq!(engine.ended_ms(), Some(200));
+        assert_eq!(engine.wpm_at(200), 0);
+        // nothing after the end counts
+        assert!(engine.press(KeyInput::Char('b'), 300).is_none());
+        assert_eq!(engine.keystrokes().len(), 2);
+    }
+
+    #[test]
+    fn wpm() {
+        // 5 chars are a word, 10 chars in 6 s is 20 wpm
+        let engine = typed("abcde fghi", ErrorPolicy::FreeCorrection, "abcde fghi");
+        assert!(engine.finished());
+        assert_eq!(engine.wpm_at(6_000), 20);
+        assert_eq!(engine.wpm_at(0), 0);
+    }
+}
@@ -0,0 +1,222 @@
+// Headless runs of the app: scripted keys go through the same dispatch as
+// `run_app`, frames are drawn on a `TestBackend` and compared to the
+// snapshots in `src/snapshots`. Run with KEYBRAH_BLESS=1 to write them
+// after an intended change to the screens.
+
+use std::{env, fs, path::PathBuf};
+
+use keybrah::clock::ManualClock;
+use ratatui::{
+    backend::TestBackend,
+    crossterm::event::{KeyCode, KeyEvent, KeyModifiers},
+    Terminal,
+};
+
+use crate::{app::App, screen::handle_key, ui::ui, update};
+
+const WIDTH: u16 = 100;
+const HEIGHT: u16 = 32;
+const SEED: u64 = 42;
+/// Time between two scripted keys.
+const KEY_MS: u64 = 150;
+
+pub struct Harness {
+    pub app: App,
+    pub clock: ManualClock,
+    pub quit: bool,
+    terminal: Terminal<TestBackend>,
+}
+
+impl Harness {
+    /// An app on the Main screen with a fixed text and a clock that only
+    /// moves with the keys.
+    pub fn new() -> Harness {
+        let clock = ManualClock::new(0);
+        let mut app = App::new();
+        app.clock = Box::new(clock.clone());
+        // finished tests stay in memory, out of the real data dir
+        app.data_dir = None;
+        app.set_seed(SEED);
+        let terminal = Terminal::new(TestBackend::new(WIDTH, HEIGHT)).unwrap();
+        Harness { app, clock, quit: false, terminal }
+    }
+
+    /// Presses `code` after `KEY_MS`, updating the app first like a turn
+    /// of `run_app` does.
+    pub fn press(&mut self, code: KeyCode) {
+        self.clock.advance(KEY_MS);
+        update(&mut self.app);
+        self.quit |= handle_key(&mut self.app, KeyEvent::new(code, KeyModifiers::NONE));
+    }
+
+    /// Presses every char of `keys` in turn.
+    pub fn type_keys(&mut self, keys: &str) {
+        for ch in keys.chars() {
+            self.press(KeyCode::Char(ch));
+        }
+    }
+
+    /// Draws a frame and returns its text, one line per row.
+    pub fn render(&mut self) -> String {
+        update(&mut self.app);
+        self.terminal.draw(|f| ui(f, &self.app)).unwrap();
+        let buffer = self.terminal.backend().buffer();
+        buffer.content()
+            .chunks(buffer.area.width as usize)
+            .map(|row| {
+                let line: String = row.iter().map(|cell| cell.symbol()).collect();
+                format!("{}\n", line.trim_end())
+            })
+            .collect()
+    }
+
+    /// Compares the current frame to the snapshot called `name`.
+    pub fn assert_snapshot(&mut self, name: &str) {
+        let frame = self.render();
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("src/snapshots")
+            .join(format!("{name}.txt"));
+        if env::var_os("KEYBRAH_BLESS").is_some() {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, &frame).unwrap();
+            return;
+        }
+        let Ok(expected) = fs::read_to_string(&path) else {
+            panic!("no snapshot at {}, run with KEYBRAH_BLESS=1 to write it\n{frame}", path.display());
+        };
+        assert!(expected == frame, "{name} doesn't match its snapshot\n--- expected\n{expected}--- got\n{frame}");
+    }
+
+    /// The text of the current test.
+    pub fn text(&self) -> String {
+        self.app.original_text.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::CurrentScreen;
+
+    #[test]
+    fn main_screen() {
+        let mut harness = Harness::new();
+        assert!(matches!(harness.app.current_screen, CurrentScreen::Main));
+        harness.assert_snapshot("main");
+    }
+
+    #[test]
+    fn editing_screen() {
+        let mut harness = Harness::new();
+        harness.press(KeyCode::Char('i'));
+        let text = harness.text();
+        // a few right keys, then a wrong one
+        harness.type_keys(&text[..5]);
+        harness.type_keys("#");
+        assert!(matches!(harness.app.current_screen, CurrentScreen::Editing));
+        harness.assert_snapshot("editing");
+    }
+
+    #[test]
+    fn scrolled_text() {
+        let mut harness = Harness::new();
+        harness.app.words = 100;
+        harness.app.set_seed(SEED);
+        harness.press(KeyCode::Char('i'));
+        let text = harness.text();
+        // well into the text, the first rows have scrolled away
+        let typed = text.len() * 3 / 5;
+        harness.type_keys(&text[..typed]);
+        assert_eq!(harness.app.engine.position(), typed);
+        assert!(!harness.render().contains(&text[..40]));
+        harness.assert_snapshot("scrolled");
+    }
+
+    #[test]
+    fn stats_screen() {
+        let mut harness = Harness::new();
+        harness.press(KeyCode::Char('i'));
+        let text = harness.text();
+        harness.type_keys(&text[..3]);
+        harness.type_keys("#");
+        harness.type_keys(&text[3..]);
+        assert!(matches!(harness.app.current_screen, CurrentScreen::Stats));
+        assert_eq!(harness.app.engine.mistakes(), 1);
+        harness.assert_snapshot("stats");
+    }
+
+    #[test]
+    fn replay_screen() {
+        let mut harness = Harness::new();
+        harness.press(KeyCode::Char('i'));
+        let text = harness.text();
+        harness.type_keys(&text);
+        harness.press(KeyCode::Char('r'));
+        assert!(matches!(harness.app.current_screen, CurrentScreen::Replay));
+        assert!(harness.app.replay_issues.is_empty());
+        // pause halfway through
+        harness.clock.advance(KEY_MS * text.len() as u64 / 2);
+        harness.press(KeyCode::Char(' '));
+        harness.assert_snapshot("replay");
+        // seeking back starts the run over up to the new point
+        let player = harness.app.player.as_mut().unwrap();
+        player.seek(-(KEY_MS as i64) * 3);
+        assert_eq!(player.engine.position(), player.replay.position_at(player.at_ms));
+    }
+
+    #[test]
+    fn imported_replay() {
+        let mut harness = Harness::new();
+        harness.press(KeyCode::Char('i'));
+        let text = harness.text();
+        harness.type_keys(&text);
+        let replay = harness.app.replays.replays.last().unwrap().clone();
+
+        // a ghost moved ahead by hand doesn't check out
+        let mut edited = replay.clone();
+        edited.keystrokes[2].position += 5;
+        assert_eq!(harness.app.verify_replay(&edited), ["keystroke 3 doesn't match"]);
+
+        let mut other = Harness::new();
+        other.app.import_replay(replay);
+        assert!(matches!(other.app.current_screen, CurrentScreen::Replay));
+        assert!(other.app.replay_issues.is_empty());
+        other.press(KeyCode::Esc);
+        other.press(KeyCode::Char('i'));
+        assert_eq!(other.text(), text);
+        assert!(other.app.ghost.is_some());
+    }
+
+    #[test]
+    fn seed_screen() {
+        let mut harness = Harness::new();
+        harness.press(KeyCode::Char('s'));
+        harness.type_keys("1234");
+        assert!(matches!(harness.app.current_screen, CurrentScreen::Seed));
+        harness.assert_snapshot("seed");
+        harness.press(KeyCode::Enter);
+        assert_eq!(harness.app.text_meta.seed, Some(1234));
+    }
+
+    #[test]
+    fn exiting_screen() {
+        let mut harness = Harness::new();
+        harness.press(KeyCode::Char('q'));
+        assert!(matches!(harness.app.current_screen, CurrentScreen::Exiting));
+        harness.assert_snapshot("exiting");
+        harness.press(KeyCode::Char('y'));
+        assert!(harness.quit);
+    }
+
+    #[test]
+    fn refused_transitions() {
+        let mut harness = Harness::new();
+        // nothing was typed, so there are no stats to show
+        assert!(!harness.app.go(CurrentScreen::Stats));
+        assert!(matches!(harness.app.current_screen, CurrentScreen::Main));
+        harness.press(KeyCode::Char('q'));
+        harness.press(KeyCode::Char('n'));
+        assert!(matches!(harness.app.current_screen, CurrentScreen::Main));
+        assert!(!harness.quit);
+    }
+}
@@ -0,0 +1,129 @@
+// Finished tests, kept on disk so pacing can use averages and bests.
+
+use std::{env, fs, io, path::{Path, PathBuf}, time::{SystemTime, UNIX_EPOCH}};
+
+use serde::{Deserialize, Serialize};
+
+const RESULTS: &str = "results.json";
+const DAILY: &str = "daily.json";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TestResult {
+    /// Results are only compared within the same mode.
+    pub mode: String,
+    pub wpm: u16,
+    pub accuracy: u16,
+    pub mistakes: u16,
+    pub words: usize,
+    pub failed: bool,
+    /// Unix seconds.
+    pub finished_at: u64,
+}
+
+/// How the runs of one mode went, for `keybrah stats`.
+#[derive(Debug, Serialize)]
+pub struct ModeSummary {
+    pub mode: String,
+    pub runs: usize,
+    pub best: Option<u16>,
+    pub average: Option<u16>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct History {
+    pub results: Vec<TestResult>,
+}
+
+impl History {
+    /// `$KEYBRAH_DATA_DIR`, else `$XDG_DATA_HOME/keybrah`, else
+    /// `~/.local/share/keybrah`.
+    pub fn data_dir() -> Option<PathBuf> {
+        if let Some(dir) = env::var_os("KEYBRAH_DATA_DIR") {
+            return Some(PathBuf::from(dir));
+        }
+        if let Some(dir) = env::var_os("XDG_DATA_HOME") {
+            return Some(PathBuf::from(dir).join("keybrah"));
+        }
+        env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share/keybrah"))
+    }
+
+    /// Reads the results stored in `dir`, a missing or unreadable file is
+    /// an empty history. With no `dir` nothing is stored at all.
+    pub fn load(dir: Option<&Path>) -> History {
+        History::load_file(dir, RESULTS)
+    }
+
+    pub fn save(&self, dir: Option<&Path>) -> io::Result<()> {
+        self.save_file(dir, RESULTS)
+    }
+
+    /// Daily challenge results are kept apart from the regular ones.
+    pub fn load_daily(dir: Option<&Path>) -> History {
+        History::load_file(dir, DAILY)
+    }
+
+    pub fn save_daily(&self, dir: Option<&Path>) -> io::Result<()> {
+        self.save_file(dir, DAILY)
+    }
+
+    fn load_file(dir: Option<&Path>, file: &str) -> History {
+        dir.and_then(|dir| fs::read_to_string(dir.join(file)).ok())
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    fn save_file(&self, dir: Option<&Path>, file: &str) -> io::Result<()> {
+        let Some(dir) = dir else { return Ok(()) };
+        fs::create_dir_all(dir)?;
+        fs::write(dir.join(file), serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn push(&mut self, result: TestResult) {
+        self.results.push(result);
+    }
+
+    fn wpms<'a>(&'a self, mode: &'a str) -> impl Iterator<Item = u16> + 'a {
+        self.results.iter()
+            .filter(move |r| r.mode == mode && !r.failed)
+            .map(|r| r.wpm)
+    }
+
+    pub fn average(&self, mode: &str) -> Option<u16> {
+        let wpms: Vec<u16> = self.wpms(mode).collect();
+        if wpms.is_empty() {
+            return None;
+        }
+        Some((wpms.iter().map(|w| *w as u32).sum::<u32>() / wpms.len() as u32) as u16)
+    }
+
+    pub fn best(&self, mode: &str) -> Option<u16> {
+        self.wpms(mode).max()
+    }
+
+    /// Every mode played, in the order they were first played.
+    pub fn summary(&self) -> Vec<ModeSummary> {
+        let mut modes: Vec<&str> = vec![];
+        for result in &self.results {
+            if !modes.contains(&result.mode.as_str()) {
+                modes.push(&result.mode);
+            }
+        }
+        modes.into_iter()
+            .map(|mode| ModeSummary {
+                mode: mode.to_string(),
+                runs: self.results.iter().filter(|r| r.mode == mode).count(),
+                best: self.best(mode),
+                average: self.average(mode),
+            })
+            .collect()
+    }
+}
+
+/// The local date as `YYYY-MM-DD`.
+pub fn today() -> String {
+    chrono::Local::now().format("%Y-%m-%d").to_string()
+}
+
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
@@ -0,0 +1,312 @@
+// Reads layouts maintained elsewhere (QMK keymap.json, XKB symbols) into
+// the `KeyLayout` the finger hints are built from.
+
+use std::{error::Error, fs, path::Path};
+
+use serde::Deserialize;
+
+use crate::keyboard::{Geometry, KeyLayout};
+
+#[derive(Deserialize)]
+struct QmkKeymap {
+    #[serde(default)]
+    keymap: Option<String>,
+    layout: String,
+    layers: Vec<Vec<String>>,
+}
+
+/// Loads a layout file, picking the importer from the extension
+/// (`.json` is QMK, anything else is XKB). QMK keymaps also tell which
+/// geometry the board has.
+pub fn load_layout(path: &Path) -> Result<(Option<Geometry>, KeyLayout), Box<dyn Error>> {
+    let contents = fs::read_to_string(path)?;
+    let name = path.file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "imported".to_string());
+    if path.extension().is_some_and(|e| e == "json") {
+        let (geometry, layout) = from_qmk(&contents, &name)?;
+        Ok((Some(geometry), layout))
+    } else {
+        Ok((None, from_xkb(&contents, &name)?))
+    }
+}
+
+/// Converts the base layer of a QMK `keymap.json`.
+pub fn from_qmk(contents: &str, name: &str) -> Result<(Geometry, KeyLayout), Box<dyn Error>> {
+    let keymap: QmkKeymap = serde_json::from_str(contents)?;
+    let base = keymap.layers.first().ok_or("keymap.json has no layers")?;
+
+    // keys per physical row of each supported LAYOUT macro
+    let (geometry, row_lengths): (Geometry, &[usize]) = match keymap.layout.as_str() {
+        "LAYOUT_60_ansi" => (Geometry::Ansi, &[14, 14, 13, 12, 8]),
+        "LAYOUT_60_iso" => (Geometry::Iso, &[14, 13, 14, 13, 8]),
+        "LAYOUT_ortho_4x12" | "LAYOUT_planck_grid" | "LAYOUT_planck_mit" =>
+            (Geometry::Ortholinear, &[12, 12, 12]),
+        "LAYOUT_split_3x6_3" => (Geometry::Split, &[12, 12, 12]),
+        other => return Err(format!("unsupported QMK layout macro `{other}`").into()),
+    };
+    if base.len() < row_lengths.iter().sum() {
+        return Err(format!("base layer has {} keys, `{}` needs at least {}",
+            base.len(), keymap.layout, row_lengths.iter().sum::<usize>()).into());
+    }
+    let mut rows = vec![];
+    let mut start = 0;
+    for len in row_lengths {
+        rows.push(&base[start..start + len]);
+        start += len;
+    }
+
+    let mut layout = KeyLayout::qwerty();
+    layout.name = keymap.keymap.unwrap_or_else(|| name.to_string());
+    let mut put = |row: usize, col: usize, keycode: &str| {
+        layout.set(row, col, qmk_keycode(keycode));
+    };
+    match geometry {
+        Geometry::Ansi | Geometry::Iso => {
+            // number row from the grave key to equals
+            for (col, keycode) in rows[0].iter().take(13).enumerate() {
+                put(0, col, keycode);
+            }
+            let top_keys = if geometry == Geometry::Iso { 12 } else { 13 };
+            for (col, keycode) in rows[1].iter().skip(1).take(top_keys).enumerate() {
+                put(1, col, keycode);
+            }
+            for (col, keycode) in rows[2].iter().skip(1).take(11).enumerate() {
+                put(2, col, keycode);
+            }
+            if geometry == Geometry::Iso {
+                // the key left of the tall enter takes the backslash position
+                put(1, 12, &rows[2][12]);
+            }
+            // skip the extra ISO key next to left shift
+            let offset = if geometry == Geometry::Iso { 2 } else { 1 };
+            for (col, keycode) in rows[3].iter().skip(offset).take(10).enumerate() {
+                put(3, col, keycode);
+            }
+        }
+        Geometry::Ortholinear | Geometry::Split => {
+            for (row, keys) in rows.iter().enumerate() {
+                for (col, keycode) in keys.iter().skip(1).take(10).enumerate() {
+                    put(row + 1, col, keycode);
+                }
+            }
+            put(2, 10, &rows[1][11]);
+        }
+    }
+    Ok((geometry, layout))
+}
+
+// Keycodes wrapped in mod-taps or layer-taps (`LSFT_T(KC_A)`, `LT(1, KC_E)`)
+// still type their inner key on a tap, shifted keycodes (`S(KC_1)`) type
+// the shifted char with or without shift.
+fn qmk_keycode(keycode: &str) -> Option<(char, char)> {
+    let mut inner = keycode.trim();
+    let mut shifted = false;
+    while let Some((wrapper, args)) = inner.strip_suffix(')').and_then(|k| k.split_once('(')) {
+        shifted |= matches!(wrapper, "S" | "LSFT" | "RSFT");
+        inner = args.rsplit(',').next().unwrap_or(args).trim();
+    }
+    let name = inner.strip_prefix("KC_")?;
+    let c = if name.len() == 1 {
+        name.chars().next()?.to_ascii_lowercase()
+    } else {
+        qmk_char(name)?
+    };
+    let (base, shift) = us_pair(c)?;
+    Some(if shifted { (shift, shift) } else { (base, shift) })
+}
+
+fn qmk_char(name: &str) -> Option<char> {
+    let c = match name {
+        "GRV" | "GRAVE" => '`',
+        "MINS" | "MINUS" => '-',
+        "EQL" | "EQUAL" => '=',
+        "LBRC" | "LEFT_BRACKET" => '[',
+        "RBRC" | "RIGHT_BRACKET" => ']',
+        "BSLS" | "BACKSLASH" | "NUHS" | "NONUS_HASH" => '\\',
+        "SCLN" | "SEMICOLON" => ';',
+        "QUOT" | "QUOTE" => '\'',
+        "COMM" | "COMMA" => ',',
+        "DOT" => '.',
+        "SLSH" | "SLASH" => '/',
+        _ => return None,
+    };
+    Some(c)
+}
+
+/// The US shifted char for `c`.
+fn us_pair(c: char) -> Option<(char, char)> {
+    let qwerty = KeyLayout::qwerty();
+    qwerty.rows.iter()
+        .flatten()
+        .flatten()
+        .find(|(base, _)| *base == c)
+        .copied()
+}
+
+/// Converts the first `xkb_symbols` block of an XKB symbols file. Keys it
+/// doesn't define keep their US position, like `include "us(basic)"` would.
+pub fn from_xkb(contents: &str, name: &str) -> Result<KeyLayout, Box<dyn Error>> {
+    let block_start = contents.find("xkb_symbols")
+        .ok_or("no xkb_symbols block found")?;
+    let block = contents[block_start..].lines()
+        .map(|line| line.split("//").next().unwrap_or("").trim())
+        .take_while(|line| *line != "};");
+
+    let mut layout = KeyLayout::qwerty();
+    layout.name = name.to_string();
+    let mut found = 0;
+    for line in block {
+        if line.starts_with("name[") {
+            if let Some(group_name) = line.split('"').nth(1) {
+                layout.name = group_name.to_string();
+            }
+            continue;
+        }
+        let Some(rest) = line.split_once("key <").map(|(_, r)| r) else { continue };
+        let Some((code, rest)) = rest.split_once('>') else { continue };
+        let Some(position) = xkb_position(code) else { continue };
+        // every level keeps its slot, an unknown keysym (dead keys and the
+        // like) doesn't move the next one down
+        let symbols: Vec<Option<char>> = rest
+            .split_once('[')
+            .and_then(|(_, r)| r.split_once(']'))
+            .map(|(syms, _)| syms.split(',').map(|s| xkb_keysym(s.trim())).collect())
+            .unwrap_or_default();
+        let Some(Some(base)) = symbols.first().copied() else { continue };
+        let shifted = symbols.get(1).copied().flatten()
+            .unwrap_or_else(|| base.to_uppercase().next().unwrap_or(base));
+        layout.set(position.0, position.1, Some((base, shifted)));
+        found += 1;
+    }
+    if found == 0 {
+        return Err("xkb_symbols block defines no typing keys".into());
+    }
+    Ok(layout)
+}
+
+fn xkb_position(code: &str) -> Option<(usize, usize)> {
+    let number = |prefix: &str| code.strip_prefix(prefix)?.parse::<usize>().ok();
+    match code {
+        "TLDE" => Some((0, 0)),
+        "BKSL" => Some((1, 12)),
+        _ if code.starts_with("AE") => number("AE").filter(|n| (1..=12).contains(n)).map(|n| (0, n)),
+        _ if code.starts_with("AD") => number("AD").filter(|n| (1..=12).contains(n)).map(|n| (1, n - 1)),
+        _ if code.starts_with("AC") => number("AC").filter(|n| (1..=11).contains(n)).map(|n| (2, n - 1)),
+        _ if code.starts_with("AB") => number("AB").filter(|n| (1..=10).contains(n)).map(|n| (3, n - 1)),
+        _ => None,
+    }
+}
+
+fn xkb_keysym(name: &str) -> Option<char> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(c);
+    }
+    if let Some(hex) = name.strip_prefix('U') {
+        return u32::from_str_radix(hex, 16).ok().and_then(char::from_u32);
+    }
+    let c = match name {
+        "space" => ' ',
+        "grave" => '`',
+        "asciitilde" => '~',
+        "exclam" => '!',
+        "at" => '@',
+        "numbersign" => '#',
+        "dollar" => '$',
+        "percent" => '%',
+        "asciicircum" => '^',
+        "ampersand" => '&',
+        "asterisk" => '*',
+        "parenleft" => '(',
+        "parenright" => ')',
+        "minus" => '-',
+        "underscore" => '_',
+        "equal" => '=',
+        "plus" => '+',
+        "bracketleft" => '[',
+        "braceleft" => '{',
+        "bracketright" => ']',
+        "braceright" => '}',
+        "backslash" => '\\',
+        "bar" => '|',
+        "semicolon" => ';',
+        "colon" => ':',
+        "apostrophe" => '\'',
+        "quotedbl" => '"',
+        "comma" => ',',
+        "less" => '<',
+        "period" => '.',
+        "greater" => '>',
+        "slash" => '/',
+        "question" => '?',
+        _ => return None,
+    };
+    Some(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn qmk_ansi() {
+        let (geometry, layout) = from_qmk(include_str!("fixtures/qmk_ansi.json"), "file").unwrap();
+        assert_eq!(geometry, Geometry::Ansi);
+        assert_eq!(layout.name, "colemak");
+        assert_eq!(layout.get(0, 1), Some(('1', '!')));
+        assert_eq!(layout.get(1, 2), Some(('f', 'F')));
+        assert_eq!(layout.get(1, 9), Some((';', ':')));
+        assert_eq!(layout.get(2, 1), Some(('r', 'R')));
+        assert_eq!(layout.get(3, 5), Some(('k', 'K')));
+    }
+
+    #[test]
+    fn qmk_iso() {
+        let (geometry, layout) = from_qmk(include_str!("fixtures/qmk_iso.json"), "qwertz").unwrap();
+        assert_eq!(geometry, Geometry::Iso);
+        assert_eq!(layout.name, "qwertz");
+        assert_eq!(layout.get(1, 5), Some(('z', 'Z')));
+        // the key left of enter, and the one next to left shift skipped
+        assert_eq!(layout.get(1, 12), Some(('\\', '|')));
+        assert_eq!(layout.get(3, 0), Some(('y', 'Y')));
+    }
+
+    #[test]
+    fn qmk_ortho() {
+        let (geometry, layout) = from_qmk(include_str!("fixtures/qmk_ortho.json"), "planck").unwrap();
+        assert_eq!(geometry, Geometry::Ortholinear);
+        assert_eq!(layout.get(2, 0), Some(('a', 'A')));
+        assert_eq!(layout.get(2, 10), Some(('\'', '"')));
+        assert_eq!(layout.get(3, 9), Some(('!', '!')));
+    }
+
+    #[test]
+    fn qmk_keycodes() {
+        assert_eq!(qmk_keycode("S(KC_1)"), Some(('!', '!')));
+        assert_eq!(qmk_keycode("LT(2, S(KC_SLSH))"), Some(('?', '?')));
+        assert_eq!(qmk_keycode("LSFT_T(KC_A)"), Some(('a', 'A')));
+        assert_eq!(qmk_keycode("KC_COMM"), Some((',', '<')));
+        assert_eq!(qmk_keycode("MO(1)"), None);
+        assert!(from_qmk(r#"{"layout": "LAYOUT_65_ansi", "layers": [[]]}"#, "x").is_err());
+    }
+
+    #[test]
+    fn xkb() {
+        let layout = from_xkb(include_str!("fixtures/colemak.xkb"), "file").unwrap();
+        assert_eq!(layout.name, "English (Colemak)");
+        assert_eq!(layout.get(1, 2), Some(('f', 'F')));
+        assert_eq!(layout.get(1, 9), Some((';', ':')));
+        assert_eq!(layout.get(2, 1), Some(('r', 'R')));
+        assert_eq!(layout.get(2, 4), Some(('ä', 'Ä')));
+        // no shifted symbol, no key from the second block, no redefinition
+        assert_eq!(layout.get(2, 0), Some(('a', 'A')));
+        assert_eq!(layout.get(1, 0), Some(('q', 'Q')));
+        assert_eq!(layout.get(3, 0), Some(('z', 'Z')));
+        // an unknown first level keeps the US key, an unknown second one
+        // doesn't take the third level's place
+        assert_eq!(layout.get(0, 0), Some(('`', '~')));
+        assert_eq!(layout.get(2, 9), Some(('o', 'O')));
+        assert!(from_xkb("xkb_symbols \"empty\" {\n};", "x").is_err());
+    }
+}
@@ -0,0 +1,434 @@
+// Physical keyboard model: where keys sit, which finger types them and
+// what each key produces on the current layout.
+
+/// Hint shown in the hint row of the hand that has to hold shift.
+pub const SHIFT_HINT: char = '⇧';
+/// Hint for keys that live on the raise layer (boards without a number row).
+pub const RAISE_HINT: char = '▲';
+/// Hint for keys that live on the lower layer (boards without a number row).
+pub const LOWER_HINT: char = '▼';
+
+/// Fingers are numbered like piano fingering, 1 (thumb) to 5 (pinky).
+pub const THUMB: char = '1';
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Geometry {
+    Ansi,
+    Iso,
+    Ortholinear,
+    Split,
+}
+
+impl Geometry {
+    pub const ALL: [Geometry; 4] = [
+        Geometry::Ansi,
+        Geometry::Iso,
+        Geometry::Ortholinear,
+        Geometry::Split,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Geometry::Ansi => "ANSI",
+            Geometry::Iso => "ISO",
+            Geometry::Ortholinear => "ORTHO",
+            Geometry::Split => "SPLIT",
+        }
+    }
+
+    pub fn next(&self) -> Geometry {
+        let i = Geometry::ALL.iter().position(|g| g == self).unwrap_or(0);
+        Geometry::ALL[(i + 1) % Geometry::ALL.len()]
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Hand {
+    Left,
+    Right,
+}
+
+impl Hand {
+    pub fn other(&self) -> Hand {
+        match self {
+            Hand::Left => Hand::Right,
+            Hand::Right => Hand::Left,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Modifier {
+    Shift,
+    Raise,
+    Lower,
+}
+
+impl Modifier {
+    pub fn hint(&self) -> char {
+        match self {
+            Modifier::Shift => SHIFT_HINT,
+            Modifier::Raise => RAISE_HINT,
+            Modifier::Lower => LOWER_HINT,
+        }
+    }
+}
+
+/// What every key position produces: four rows (number, top, home, bottom)
+/// of `(key, shifted key)` pairs in the positions of a US ANSI board.
+/// `None` marks a position the layout leaves empty.
+#[derive(Clone, Debug)]
+pub struct KeyLayout {
+    pub name: String,
+    pub rows: Vec<Vec<Option<(char, char)>>>,
+}
+
+impl KeyLayout {
+    pub fn qwerty() -> KeyLayout {
+        KeyLayout::from_rows("qwerty", [
+            ("`1234567890-=", "~!@#$%^&*()_+"),
+            ("qwertyuiop[]\\", "QWERTYUIOP{}|"),
+            ("asdfghjkl;'", "ASDFGHJKL:\""),
+            ("zxcvbnm,./", "ZXCVBNM<>?"),
+        ])
+    }
+
+    pub fn colemak() -> KeyLayout {
+        KeyLayout::from_rows("colemak", [
+            ("`1234567890-=", "~!@#$%^&*()_+"),
+            ("qwfpgjluy;[]\\", "QWFPGJLUY:{}|"),
+            ("arstdhneio'", "ARSTDHNEIO\""),
+            ("zxcvbkm,./", "ZXCVBKM<>?"),
+        ])
+    }
+
+    pub fn dvorak() -> KeyLayout {
+        KeyLayout::from_rows("dvorak", [
+            ("`1234567890[]", "~!@#$%^&*(){}"),
+            ("',.pyfgcrl/=\\", "\"<>PYFGCRL?+|"),
+            ("aoeuidhtns-", "AOEUIDHTNS_"),
+            (";qjkxbmwvz", ":QJKXBMWVZ"),
+        ])
+    }
+
+    /// The built-in layout called `name`.
+    /// Names `named` knows.
+    pub const NAMES: [&str; 3] = ["qwerty", "colemak", "dvorak"];
+
+    pub fn named(name: &str) -> Option<KeyLayout> {
+        match name {
+            "qwerty" => Some(KeyLayout::qwerty()),
+            "colemak" => Some(KeyLayout::colemak()),
+            "dvorak" => Some(KeyLayout::dvorak()),
+            _ => None,
+        }
+    }
+
+    fn from_rows(name: &str, rows: [(&str, &str); 4]) -> KeyLayout {
+        KeyLayout {
+            name: name.to_string(),
+            rows: rows.iter()
+                .map(|(keys, shifted)| keys.chars().zip(shifted.chars()).map(Some).collect())
+                .collect(),
+        }
+    }
+
+    /// The pair at `row`/`col`, or `None` when the layout leaves it empty.
+    pub fn get(&self, row: usize, col: usize) -> Option<(char, char)> {
+        self.rows.get(row).and_then(|r| r.get(col)).copied().flatten()
+    }
+
+    /// Puts `pair` at `row`/`col`, ignoring positions outside the layout.
+    pub fn set(&mut self, row: usize, col: usize, pair: Option<(char, char)>) {
+        if let Some(slot) = self.rows.get_mut(row).and_then(|r| r.get_mut(col)) {
+            *slot = pair;
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Key {
+    pub label: String,
+    pub row: u16,
+    /// Offset and width are in quarter key units.
+    pub x: u16,
+    pub width: u16,
+    pub hand: Hand,
+    pub finger: char,
+    pub base: Option<char>,
+    pub shifted: Option<char>,
+    pub raised: Option<char>,
+    pub lowered: Option<char>,
+    pub modifier: Option<Modifier>,
+}
+
+impl Key {
+    fn new(label: &str, row: u16, x: u16, width: u16, hand: Hand, finger: char) -> Key {
+        Key { label: label.to_string(), row, x, width, hand, finger,
+              base: None, shifted: None, raised: None, lowered: None, modifier: None }
+    }
+
+    fn typing(pair: Option<(char, char)>, row: u16, x: u16, width: u16,
+              hand: Hand, finger: char) -> Key {
+        let mut key = Key::new("", row, x, width, hand, finger);
+        if let Some((base, shifted)) = pair {
+            key.label = base.to_string();
+            key.base = Some(base);
+            key.shifted = Some(shifted);
+        }
+        key
+    }
+
+    /// The char this key types while `modifier` is held.
+    pub fn with(&self, modifier: Modifier) -> Option<char> {
+        match modifier {
+            Modifier::Shift => self.shifted,
+            Modifier::Raise => self.raised,
+            Modifier::Lower => self.lowered,
+        }
+    }
+
+    fn modifier(label: &str, modifier: Modifier, row: u16, x: u16, width: u16,
+                hand: Hand, finger: char) -> Key {
+        let mut key = Key::new(label, row, x, width, hand, finger);
+        key.modifier = Some(modifier);
+        key
+    }
+}
+
+/// Which key types a char and which modifier key has to be held with it.
+#[derive(Clone, Copy, Debug)]
+pub struct Hint {
+    pub key: usize,
+    pub modifier: Option<(Modifier, usize)>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Keyboard {
+    pub geometry: Geometry,
+    pub layout: KeyLayout,
+    pub keys: Vec<Key>,
+}
+
+impl Keyboard {
+    pub fn new(geometry: Geometry, layout: KeyLayout) -> Keyboard {
+        let keys = match geometry {
+            Geometry::Ansi | Geometry::Iso => staggered_keys(geometry, &layout),
+            Geometry::Ortholinear | Geometry::Split => grid_keys(geometry, &layout),
+        };
+        Keyboard { geometry, layout, keys }
+    }
+
+    /// Finds the key for `c`, preferring the plain key over modified ones.
+    pub fn hint(&self, c: char) -> Option<Hint> {
+        if let Some(key) = self.keys.iter().position(|k| k.base == Some(c)) {
+            return Some(Hint { key, modifier: None });
+        }
+        for modifier in [Modifier::Shift, Modifier::Raise, Modifier::Lower] {
+            if let Some(key) = self.keys.iter().position(|k| k.with(modifier) == Some(c)) {
+                let modifier_key = self.modifier_key(modifier, self.keys[key].hand.other())
+                    .or_else(|| self.modifier_key(modifier, self.keys[key].hand))?;
+                return Some(Hint { key, modifier: Some((modifier, modifier_key)) });
+            }
+        }
+        None
+    }
+
+    /// The fingers for `c` shown above (right hand) and below (left hand)
+    /// the text. Modifiers are held by the opposite hand, so their hint
+    /// goes there.
+    pub fn fingers(&self, c: char) -> (char, char) {
+        let (mut right, mut left) = (' ', ' ');
+        if let Some(hint) = self.hint(c) {
+            let key = &self.keys[hint.key];
+            match key.hand {
+                Hand::Right => right = key.finger,
+                Hand::Left => left = key.finger,
+            }
+            if let Some((modifier, modifier_key)) = hint.modifier {
+                match self.keys[modifier_key].hand {
+                    Hand::Right => right = modifier.hint(),
+                    Hand::Left => left = modifier.hint(),
+                }
+            }
+        }
+        (right, left)
+    }
+
+    /// Every char this board can type, in key order.
+    pub fn chars(&self) -> String {
+        let mut chars = String::new();
+        for key in &self.keys {
+            for c in [key.base, key.shifted, key.raised, key.lowered].into_iter().flatten() {
+                if !chars.contains(c) && self.hint(c).is_some() {
+                    chars.push(c);
+                }
+            }
+        }
+        chars
+    }
+
+    fn modifier_key(&self, modifier: Modifier, hand: Hand) -> Option<usize> {
+        self.keys.iter()
+            .position(|k| k.modifier == Some(modifier) && k.hand == hand)
+    }
+
+    /// Number of rendered rows, the thumb row included.
+    pub fn height(&self) -> u16 {
+        self.keys.iter().map(|k| k.row).max().unwrap_or(0)
+            - self.keys.iter().map(|k| k.row).min().unwrap_or(0) + 1
+    }
+}
+
+fn hand_and_finger(fingers: &str, col: usize) -> (Hand, char) {
+    let (left, right) = fingers.split_once('|').unwrap_or((fingers, ""));
+    let left: Vec<char> = left.chars().collect();
+    if col < left.len() {
+        (Hand::Left, left[col])
+    } else {
+        (Hand::Right, right.chars().nth(col - left.len()).unwrap_or('5'))
+    }
+}
+
+// Row-staggered boards, widths follow the usual 60% layout.
+fn staggered_keys(geometry: Geometry, layout: &KeyLayout) -> Vec<Key> {
+    let iso = geometry == Geometry::Iso;
+    let mut keys = vec![];
+    let row_keys = |row: u16, start: u16, count: usize, fingers: &str,
+                        keys: &mut Vec<Key>| {
+        let mut x = start;
+        for col in 0..count {
+            let (hand, finger) = hand_and_finger(fingers, col);
+            // ANSI backslash is 1.5u wide
+            let width = if !iso && row == 1 && col == 12 { 6 } else { 4 };
+            keys.push(Key::typing(layout.get(row as usize, col), row, x, width, hand, finger));
+            x += width;
+        }
+        x
+    };
+
+    let x = row_keys(0, 0, 13, "5543222|234555", &mut keys);
+    keys.push(Key::new("bksp", 0, x, 8, Hand::Right, '5'));
+
+    keys.push(Key::new("tab", 1, 0, 6, Hand::Left, '5'));
+    if iso {
+        let x = row_keys(1, 6, 12, "54322|2234555", &mut keys);
+        keys.push(Key::new("ent", 1, x, 6, Hand::Right, '5'));
+    } else {
+        row_keys(1, 6, 13, "54322|22345555", &mut keys);
+    }
+
+    keys.push(Key::new("caps", 2, 0, 7, Hand::Left, '5'));
+    let mut x = row_keys(2, 7, 11, "54322|223455", &mut keys);
+    if iso {
+        // the backslash key moves down next to the tall enter
+        keys.push(Key::typing(layout.get(1, 12), 2, x, 4, Hand::Right, '5'));
+        x += 4;
+        keys.push(Key::new("", 2, x, 5, Hand::Right, '5'));
+    } else {
+        keys.push(Key::new("enter", 2, x, 9, Hand::Right, '5'));
+    }
+
+    if iso {
+        keys.push(Key::modifier("shft", Modifier::Shift, 3, 0, 5, Hand::Left, '5'));
+        keys.push(Key::new("", 3, 5, 4, Hand::Left, '5'));
+    } else {
+        keys.push(Key::modifier("shift", Modifier::Shift, 3, 0, 9, Hand::Left, '5'));
+    }
+    let x = row_keys(3, 9, 10, "54322|22345", &mut keys);
+    keys.push(Key::modifier("shift", Modifier::Shift, 3, x, 11, Hand::Right, '5'));
+
+    let mut space = Key::new("space", 4, 15, 25, Hand::Left, THUMB);
+    space.base = Some(' ');
+    keys.push(space);
+    keys
+}
+
+// Ortholinear (Planck-like 4x12) and columnar split (Corne-like 3x6+3)
+// boards have no number row, digits and symbols sit on the raise and
+// lower layers of the top and home rows.
+fn grid_keys(geometry: Geometry, layout: &KeyLayout) -> Vec<Key> {
+    let split = geometry == Geometry::Split;
+    let fingers = "554322|223455";
+    let x_of = |col: u16| col * 4 + if split && col >= 6 { 8 } else { 0 };
+    let mut keys = vec![];
+
+    let outer = [["tab", "bsp"], ["esc", ""], ["sft", "sft"]];
+    for row in 1..=3u16 {
+        for col in 0..12u16 {
+            let (hand, finger) = hand_and_finger(fingers, col as usize);
+            let pair = match col {
+                1..=10 => layout.get(row as usize, col as usize - 1),
+                // the quote key keeps its place on the home row
+                11 if row == 2 => layout.get(2, 10),
+                _ => None,
+            };
+            let mut key = if pair.is_some() {
+                Key::typing(pair, row, x_of(col), 4, hand, finger)
+            } else {
+                let label = outer[row as usize - 1][if col == 0 { 0 } else { 1 }];
+                if label == "sft" {
+                    Key::modifier(label, Modifier::Shift, row, x_of(col), 4, hand, finger)
+                } else {
+                    Key::new(label, row, x_of(col), 4, hand, finger)
+                }
+            };
+            let layer = match (row, col) {
+                (1, 0) => layout.get(0, 0),
+                (1, 1..=10) => layout.get(0, col as usize),
+                (2, 6..=7) => layout.get(0, col as usize + 5),
+                (2, 8..=10) => layout.get(1, col as usize + 2),
+                _ => None,
+            };
+            if let Some((raised, lowered)) = layer {
+                key.raised = Some(raised);
+                key.lowered = Some(lowered);
+            }
+            keys.push(key);
+        }
+    }
+
+    let thumbs: Vec<(u16, u16, &str, Option<Modifier>)> = if split {
+        vec![(3, 4, "lwr", Some(Modifier::Lower)), (4, 4, "rse", Some(Modifier::Raise)),
+             (5, 4, "spc", None), (6, 4, "ent", None),
+             (7, 4, "rse", Some(Modifier::Raise)), (8, 4, "lwr", Some(Modifier::Lower))]
+    } else {
+        vec![(3, 4, "lwr", Some(Modifier::Lower)), (4, 4, "rse", Some(Modifier::Raise)),
+             (5, 8, "space", None),
+             (7, 4, "rse", Some(Modifier::Raise)), (8, 4, "lwr", Some(Modifier::Lower))]
+    };
+    for (col, width, label, modifier) in thumbs {
+        let hand = if col < 6 { Hand::Left } else { Hand::Right };
+        let mut key = Key::new(label, 4, x_of(col), width, hand, THUMB);
+        key.modifier = modifier;
+        if matches!(label, "space" | "spc") {
+            key.base = Some(' ');
+        }
+        keys.push(key);
+    }
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_printable_char_has_a_hint() {
+        for geometry in Geometry::ALL {
+            let keyboard = Keyboard::new(geometry, KeyLayout::qwerty());
+            for c in ' '..='~' {
+                let hint = keyboard.hint(c);
+                assert!(hint.is_some(), "no hint for {c:?} on {}", geometry.name());
+                assert_ne!(keyboard.fingers(c), (' ', ' '), "no fingers for {c:?} on {}", geometry.name());
+                // modifiers are held by the other hand
+                if let Some(Hint { key, modifier: Some((_, modifier_key)) }) = hint {
+                    assert_ne!(keyboard.keys[key].hand, keyboard.keys[modifier_key].hand,
+                        "{c:?} on {}", geometry.name());
+                }
+            }
+            assert_eq!(keyboard.fingers('A'), (SHIFT_HINT, '5'), "{}", geometry.name());
+            assert_eq!(keyboard.fingers('a'), (' ', '5'), "{}", geometry.name());
+        }
+    }
+}
@@ -0,0 +1,6 @@
+//! The typing engine behind keybrah, without the terminal: feed an
+//! `engine::Engine` keys with their timestamps and read the test back.
+//! `clock` hands out those timestamps.
+
+pub mod clock;
+pub mod engine;
@@ -1,22 +1,68 @@
 mod app;
+mod cli;
+#[cfg(test)]
+mod harness;
+mod history;
+mod import;
+mod keyboard;
+mod net;
+mod plugin;
+mod replay;
+mod report;
+mod screen;
+mod source;
 mod ui;
+mod words;
 
-use std::{error::Error, io, time};
+use std::{env, error::Error, ffi::OsString, io, path::{Path, PathBuf}, process, time};
 use ratatui::{
     backend::{Backend, CrosstermBackend},
     crossterm::{
-        event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind},
+        event::{self, DisableMouseCapture, EnableMouseCapture, Event},
         execute,
         terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     },
     Terminal,
 };
 use crate::{
-    app::{App, CurrentScreen, CurrentlyEditing},
+    app::{App, CurrentScreen},
+    cli::{Args, Command, RaceRole, USAGE},
+    history::History,
+    keyboard::KeyLayout,
+    net::{Race, DEFAULT_PORT},
+    plugin::Plugin,
+    replay::Replays,
+    screen::handle_key,
+    source::FileSource,
     ui::ui,
 };
 
+const PACE_TICK: time::Duration = time::Duration::from_millis(50);
+
 fn main() -> Result<(), Box<dyn Error>> {
+    let args = match cli::parse(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprint!("keybrah: {err}\n\n{USAGE}");
+            process::exit(2);
+        }
+    };
+    if args.help {
+        print!("{USAGE}");
+        return Ok(());
+    }
+    if args.command == Some(Command::Stats) {
+        return print_stats(args.json);
+    }
+    // like a bad argument, nothing has been drawn yet
+    let mut app = match setup(&args) {
+        Ok(app) => app,
+        Err(err) => {
+            eprintln!("keybrah: {err}");
+            process::exit(2);
+        }
+    };
+
     // setup terminal
     enable_raw_mode()?;
     let mut stderr = io::stderr();
@@ -24,9 +70,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     let backend = CrosstermBackend::new(stderr);
     let mut terminal = Terminal::new(backend)?;
 
-    // create app and run the loop
-    let mut app = App::new();
-    app.add_map();
+    // run the loop
     let res = run_app(&mut terminal, &mut app);
 
     // since app has changed the state of the user’s terminal, we need to undo
@@ -35,7 +79,10 @@ fn main() -> Result<(), Box<dyn Error>> {
     terminal.show_cursor()?;
 
     if let Ok(do_print) = res {
-        if do_print {
+        // the terminal is drawn on stderr, so stdout only has this
+        if do_print && args.json {
+            println!("{}", serde_json::to_string(&app.report)?);
+        } else if do_print {
             println!("Exit!");
         }
     } else if let Err(err) = res {
@@ -45,136 +92,156 @@ fn main() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// The app as the arguments and KEYBRAH_* variables ask, before the
+/// terminal is taken over. Errors say what to fix.
+fn setup(args: &Args) -> Result<App, String> {
+    let mut app = App::new();
+    app.history = History::load(app.data_dir.as_deref());
+    app.daily_results = History::load_daily(app.data_dir.as_deref());
+    app.replays = Replays::load(app.data_dir.as_deref());
+    // a built-in layout by name, else a QMK keymap.json or XKB symbols
+    // file
+    if let Some(name) = &args.layout
+        && let Some(layout) = KeyLayout::named(name) {
+        app.set_layout(None, layout);
+    } else if let Some(path) = args.layout.clone().map(OsString::from).or_else(|| env::var_os("KEYBRAH_LAYOUT")) {
+        let path = Path::new(&path);
+        let (geometry, layout) = import::load_layout(path).map_err(|err| {
+            format!("can't read layout {}: {err} (built in: {})", path.display(), KeyLayout::NAMES.join(", "))
+        })?;
+        app.set_layout(geometry, layout);
+    }
+    // external text generators, --lang or KEYBRAH_SOURCE picks the source
+    // regular texts come from
+    let plugins = match env::var_os("KEYBRAH_PLUGINS") {
+        Some(paths) if args.plugins.is_empty() => env::split_paths(&paths).collect(),
+        _ => args.plugins.clone(),
+    };
+    for path in plugins {
+        app.sources.register(Box::new(Plugin::new(&path)));
+    }
+    if let Some(name) = args.lang.clone().or_else(|| env::var("KEYBRAH_SOURCE").ok()) {
+        let Some(source) = app.sources.find(&name) else {
+            return Err(format!("no text source {name}, there is {}", app.sources.ids().join(", ")));
+        };
+        app.source = source.id().to_string();
+    }
+    match &args.command {
+        Some(Command::Words(words)) => app.words = *words,
+        Some(Command::Time(secs)) => app.time_limit = Some(*secs),
+        Some(Command::File(path)) => {
+            let file = FileSource::open(path)
+                .map_err(|err| format!("can't read {}: {err}", path.display()))?;
+            app.sources.register(Box::new(file));
+            app.source = FileSource::ID.to_string();
+        }
+        Some(Command::Replay(_) | Command::Race(_) | Command::Stats) | None => {}
+    }
+    app.text_options = args.options;
+    let loaded = match args.seed {
+        Some(seed) => app.set_seed(seed),
+        None => app.regenerate(),
+    };
+    // don't quietly fall back on the text App::new made
+    if !loaded {
+        return Err(app.status.take().unwrap_or_default());
+    }
+    // a shared replay opens straight on the replay screen
+    let replay = match &args.command {
+        Some(Command::Replay(path)) => Some(path.clone()),
+        _ => env::var_os("KEYBRAH_REPLAY").map(PathBuf::from),
+    };
+    if let Some(path) = replay {
+        let replay = replay::import(&path)
+            .map_err(|err| format!("can't open replay {}: {err}", path.display()))?;
+        app.import_replay(replay);
+    }
+    // LAN race: host a lobby, join one or watch one
+    let name = args.name.clone()
+        .or_else(|| env::var("KEYBRAH_NAME").ok())
+        .or_else(|| env::var("USER").ok())
+        .unwrap_or_else(|| "player".to_string());
+    let role = match &args.command {
+        Some(Command::Race(role)) => Some(role.clone()),
+        _ => env_race()?,
+    };
+    app.race = match role {
+        Some(RaceRole::Host(port)) => Some(Race::host(port, &name)
+            .map_err(|err| format!("can't host on port {port}: {err}"))?),
+        Some(RaceRole::Join(address)) => Some(Race::join(&address, &name)
+            .map_err(|err| format!("can't join {address}: {err}"))?),
+        Some(RaceRole::Watch(address)) => Some(Race::watch(&address, &name)
+            .map_err(|err| format!("can't watch {address}: {err}"))?),
+        None => None,
+    };
+    Ok(app)
+}
+
+/// The race KEYBRAH_HOST (a port, empty for the default), KEYBRAH_JOIN or
+/// KEYBRAH_WATCH ask for.
+fn env_race() -> Result<Option<RaceRole>, String> {
+    if let Ok(port) = env::var("KEYBRAH_HOST") {
+        if port.is_empty() {
+            return Ok(Some(RaceRole::Host(DEFAULT_PORT)));
+        }
+        let port = port.parse().map_err(|_| format!("KEYBRAH_HOST needs a port number, not {port}"))?;
+        return Ok(Some(RaceRole::Host(port)));
+    }
+    Ok(env::var("KEYBRAH_JOIN").map(RaceRole::Join)
+        .or_else(|_| env::var("KEYBRAH_WATCH").map(RaceRole::Watch))
+        .ok())
+}
+
 fn run_app <B: Backend> (terminal: & mut Terminal<B>,
                          app: &mut App) -> io::Result<bool> {
-    let mut current_text: Vec<char> = app.current_text.chars().rev().collect();
     loop {
+        update(app);
         // take a frame (f) and pass to ui function to draw
         terminal.draw(|f| ui(f, app))?; // immutable borrow
-        if let Event::Key(key) = event::read()? {
-            if key.kind == event::KeyEventKind::Release {
-                continue;
-            }
-            match app.current_screen {
-                CurrentScreen::Main => match key.code {
-                    KeyCode::Char('i') => {
-                        app.start_time = Some(time::Instant::now());
-                        app.current_screen = CurrentScreen::Editing;
-                        app.currently_editing = Some(CurrentlyEditing::Key);
-                    }
-                    KeyCode::Char('q') => {
-                        app.current_screen = CurrentScreen::Exiting;
-                    }
-                    _ => {}
-                },
-
-                CurrentScreen::Stats => match key.code {
-                    KeyCode::Char('i') => {
-                        app.start_time = Some(time::Instant::now());
-                        app.current_screen = CurrentScreen::Editing;
-                        app.currently_editing = Some(CurrentlyEditing::Key);
-                    }
-                    KeyCode::Char('q') => {
-                        app.current_screen = CurrentScreen::Exiting;
-                    }
-                    KeyCode::Esc | KeyCode::Enter => {
-                        app.reset();
-                    }
-
-                    _ => {}
-                },
-
-                CurrentScreen::Exiting => match key.code {
-                    KeyCode::Char('y')  => { return Ok(true) },
-                    KeyCode::Char('n')  => { 
-                        app.reset();
-                        app.current_screen = CurrentScreen::Main; }
-                    _ => {}
-                }
-                CurrentScreen::Editing if key.kind == KeyEventKind::Press => {
-                    match key.code {
-                        KeyCode::Enter => {
-                            if let Some(editing) = &app.currently_editing {
-                                match editing {
-                                    CurrentlyEditing::Key => {
-                                        app.currently_editing = Some(CurrentlyEditing::Value);
-                                    }
-                                    CurrentlyEditing::Value=> {
-                                        app.save_key_value();
-                                        app.current_screen = CurrentScreen::Main;
-                                    }
-                                }
-                            }
-                        }
-                        KeyCode::Backspace => {
-                            if let Some(editing) = &app.currently_editing {
-                                match editing {
-                                    CurrentlyEditing::Key => {
-                                        app.key_input.pop();
-                                    }
-                                    CurrentlyEditing::Value=> {
-                                        app.value_input.pop();
-                                    }
-                                }
-                            }
-                        }
-                        KeyCode::Esc => {
-                            app.reset();
-                            current_text = app.current_text.chars().rev().collect();
-                        }
-                        KeyCode::Tab => {app.toggle_editing()}
-                        KeyCode::Char(value) => {
-                            if let Some(editing) = &app.currently_editing {
-                                match editing {
-                                    CurrentlyEditing::Key => {
-                                        current_text = handle_insert(current_text,
-                                        app, value);
-                                        app.update_wpm();
-                                    }
-                                    CurrentlyEditing::Value => {
-                                        // app.value_input.push(value);
-                                    }
-                                }
-                            }
-                        }
-                        _ => {}
-                    }
-                }
-                _ => {}
-            }
+        // the pace and ghost carets, the clock of a timed test and replays
+        // move on their own, so redraw even without input
+        let pacing = (app.pace_wpm().is_some() || app.ghost.is_some() || app.time_limit.is_some())
+            && matches!(app.current_screen, CurrentScreen::Editing);
+        let replaying = matches!(app.current_screen, CurrentScreen::Replay);
+        // other racers move and the host may start at any time
+        let racing = app.race.is_some();
+        if (pacing || replaying || racing) && !event::poll(PACE_TICK)? {
+            continue;
+        }
+        if let Event::Key(key) = event::read()?
+            && handle_key(app, key) {
+            return Ok(true);
         }
     }
 }
 
-fn handle_insert(mut current_text: Vec<char>, app: &mut App, value: char) -> Vec<char> {
-    if value == current_text[current_text.len() - 1] {
-        if app.key_input.len() > 0 {
-            let ind = app.key_input.len() + 1;
-            if ind != app.right_nums.len() {
-                app.lefts[ind - 1] = ' ';
-                app.rights[ind - 1] = ' ';
-                app.rights[ind] = app.right_nums[ind];
-                app.lefts[ind] = app.left_nums[ind];
-            } else { app.go_stats();
-                return app.current_text.clone().chars().collect()};
-        }
-        app.wrong = false;
-        app.cursor = app.current_text
-            .as_bytes()[1] as char;
-        let char = current_text.pop();
-        app.key_input.push(char.unwrap());
-        current_text.reverse();
-        let new_str = current_text.clone().into_iter()
-            .map(|i| i.to_string())
-            .collect::<String>();
-        app.edit_text(&new_str);
-        current_text.reverse();
-    } else {
-        if !app.wrong {
-            app.wrong = true;
-            app.mistakes += 1;
-            app.update_accuracy();
-        }
+/// What changes between frames without a key: replay playback, the race
+/// and the time of a timed test.
+fn update(app: &mut App) {
+    let now_ms = app.clock.now_ms();
+    if let Some(player) = &mut app.player {
+        player.tick(now_ms);
+    }
+    app.poll_race();
+    app.check_time();
+}
+
+/// `keybrah stats`: every mode played with its runs, best and average.
+fn print_stats(json: bool) -> Result<(), Box<dyn Error>> {
+    let dir = History::data_dir();
+    let mut modes = History::load(dir.as_deref()).summary();
+    modes.extend(History::load_daily(dir.as_deref()).summary());
+    if json {
+        println!("{}", serde_json::to_string(&modes)?);
+        return Ok(());
+    }
+    if modes.is_empty() {
+        println!("no results yet");
     }
-    return current_text
+    let wpm = |wpm: Option<u16>| wpm.map(|w| w.to_string()).unwrap_or("-".to_string());
+    for mode in modes {
+        println!("{:<44} {:>4} runs   best {:>4}   avg {:>4}",
+            mode.mode, mode.runs, wpm(mode.best), wpm(mode.average));
+    }
+    Ok(())
 }
@@ -0,0 +1,399 @@
+// LAN races: one instance hosts a lobby on a TCP port, the others join it.
+// Messages are JSON, one per line.
+
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        mpsc::{self, Sender},
+        Arc, Mutex, MutexGuard,
+    },
+    thread,
+};
+
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_PORT: u16 = 7878;
+
+/// The host always races as id 0.
+const HOST_ID: u32 = 0;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Racer {
+    pub id: u32,
+    pub name: String,
+    /// Index into the race text the racer's cursor is at.
+    pub position: usize,
+    pub wpm: u16,
+    pub accuracy: u16,
+    /// Milliseconds from the start to the last key, once done.
+    pub finished_ms: Option<u64>,
+}
+
+impl Racer {
+    fn new(id: u32, name: &str) -> Racer {
+        Racer { id, name: name.to_string(), position: 0, wpm: 0, accuracy: 100, finished_ms: None }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Message {
+    Hello {
+        name: String,
+        /// Spectators see the race without taking part.
+        #[serde(default)]
+        spectator: bool,
+    },
+    Welcome { id: u32 },
+    Lobby {
+        racers: Vec<Racer>,
+        #[serde(default)]
+        spectators: usize,
+    },
+    Start { text: String },
+    Progress { position: usize, wpm: u16, accuracy: u16, finished_ms: Option<u64> },
+}
+
+#[derive(Default)]
+struct Shared {
+    racers: Vec<Racer>,
+    // client only, the text of a start not yet handed to the app
+    text: Option<String>,
+    // host only, the text of the race running, for anyone joining late
+    started: Option<String>,
+    // host only, every update is broadcast to these, each client has a
+    // thread of its own writing them out
+    clients: Vec<(u32, Sender<String>)>,
+    spectators: usize,
+    next_id: u32,
+    closed: bool,
+}
+
+impl Shared {
+    fn broadcast(&mut self, message: &Message) {
+        let line = line(message);
+        // clients whose writer stopped have left
+        self.clients.retain(|(_, outbox)| outbox.send(line.clone()).is_ok());
+    }
+
+    fn lobby(&self) -> Message {
+        Message::Lobby { racers: self.racers.clone(), spectators: self.spectators }
+    }
+}
+
+/// One side of a race, the host or a client. Both keep the latest
+/// standings in `racers`.
+pub struct Race {
+    shared: Arc<Mutex<Shared>>,
+    pub id: u32,
+    pub is_host: bool,
+    pub spectating: bool,
+    // client only, where progress goes
+    stream: Option<TcpStream>,
+    pub address: String,
+}
+
+impl Race {
+    /// Opens a lobby on `port` with the host as the first racer.
+    pub fn host(port: u16, name: &str) -> io::Result<Race> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        let address = listener.local_addr()?.to_string();
+        let shared = Arc::new(Mutex::new(Shared {
+            racers: vec![Racer::new(HOST_ID, name)],
+            next_id: HOST_ID + 1,
+            ..Shared::default()
+        }));
+        let accepting = Arc::clone(&shared);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let shared = Arc::clone(&accepting);
+                thread::spawn(move || serve_client(stream, shared));
+            }
+        });
+        Ok(Race { shared, id: HOST_ID, is_host: true, spectating: false, stream: None, address })
+    }
+
+    /// Joins the lobby at `address` and waits to be let in.
+    pub fn join(address: &str, name: &str) -> io::Result<Race> {
+        Race::connect(address, name, false)
+    }
+
+    /// Watches the race at `address` without taking part.
+    pub fn watch(address: &str, name: &str) -> io::Result<Race> {
+        Race::connect(address, name, true)
+    }
+
+    fn connect(address: &str, name: &str, spectator: bool) -> io::Result<Race> {
+        let addr = address.to_socket_addrs()?.next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such address"))?;
+        let mut stream = TcpStream::connect(addr)?;
+        send(&mut stream, &Message::Hello { name: name.to_string(), spectator })?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let id = match read(&mut reader)? {
+            Message::Welcome { id } => id,
+            other => return Err(io::Error::new(io::ErrorKind::InvalidData,
+                format!("expected a welcome, got {other:?}"))),
+        };
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        let listening = Arc::clone(&shared);
+        thread::spawn(move || {
+            while let Ok(message) = read(&mut reader) {
+                let mut shared = lock(&listening);
+                match message {
+                    Message::Lobby { racers, spectators } => {
+                        shared.racers = racers;
+                        shared.spectators = spectators;
+                    }
+                    Message::Start { text } => shared.text = Some(text),
+                    _ => {}
+                }
+            }
+            lock(&listening).closed = true;
+        });
+        Ok(Race {
+            shared,
+            id,
+            is_host: false,
+            spectating: spectator,
+            stream: Some(stream),
+            address: address.to_string(),
+        })
+    }
+
+    /// Host only: sends everyone the text and starts the race.
+    pub fn start(&self, text: &str) {
+        let mut shared = lock(&self.shared);
+        shared.started = Some(text.to_string());
+        for racer in shared.racers.iter_mut() {
+            racer.position = 0;
+            racer.wpm = 0;
+            racer.accuracy = 100;
+            racer.finished_ms = None;
+        }
+        shared.broadcast(&Message::Start { text: text.to_string() });
+        let lobby = shared.lobby();
+        shared.broadcast(&lobby);
+    }
+
+    /// The text of a race the host just started, handed out once per
+    /// start. Always `None` on the host, which started it.
+    pub fn take_text(&self) -> Option<String> {
+        lock(&self.shared).text.take()
+    }
+
+    pub fn racers(&self) -> Vec<Racer> {
+        lock(&self.shared).racers.clone()
+    }
+
+    pub fn spectators(&self) -> usize {
+        lock(&self.shared).spectators
+    }
+
+    /// Whether the host went away.
+    pub fn closed(&self) -> bool {
+        lock(&self.shared).closed
+    }
+
+    pub fn send_progress(&mut self, position: usize, wpm: u16, accuracy: u16,
+                         finished_ms: Option<u64>) {
+        if let Some(stream) = &mut self.stream {
+            let message = Message::Progress { position, wpm, accuracy, finished_ms };
+            if send(stream, &message).is_err() {
+                lock(&self.shared).closed = true;
+            }
+        } else {
+            let mut shared = lock(&self.shared);
+            update(&mut shared, self.id, position, wpm, accuracy, finished_ms);
+        }
+    }
+
+    /// Finished racers by time, then everyone else by how far they got.
+    pub fn ranking(&self) -> Vec<Racer> {
+        let mut racers = self.racers();
+        racers.sort_by_key(|r| (r.finished_ms.is_none(), r.finished_ms, usize::MAX - r.position));
+        racers
+    }
+}
+
+fn serve_client(stream: TcpStream, shared: Arc<Mutex<Shared>>) {
+    let Ok(mut writer) = stream.try_clone() else { return };
+    let mut reader = BufReader::new(stream);
+    let Ok(Message::Hello { name, spectator }) = read(&mut reader) else { return };
+    // the host writes with its lock held, a client that stops reading
+    // must only hold up its own thread
+    let (outbox, lines) = mpsc::channel::<String>();
+    thread::spawn(move || {
+        for line in lines {
+            if writeln!(writer, "{line}").is_err() {
+                break;
+            }
+        }
+    });
+    let id = {
+        let mut shared = lock(&shared);
+        let id = shared.next_id;
+        shared.next_id += 1;
+        let _ = outbox.send(line(&Message::Welcome { id }));
+        // someone joining mid-race still gets the text
+        if let Some(text) = shared.started.clone() {
+            let _ = outbox.send(line(&Message::Start { text }));
+        }
+        if spectator {
+            shared.spectators += 1;
+        } else {
+            shared.racers.push(Racer::new(id, &name));
+        }
+        shared.clients.push((id, outbox));
+        let lobby = shared.lobby();
+        shared.broadcast(&lobby);
+        id
+    };
+    while let Ok(message) = read(&mut reader) {
+        if let Message::Progress { position, wpm, accuracy, finished_ms } = message
+            && !spectator {
+            update(&mut lock(&shared), id, position, wpm, accuracy, finished_ms);
+        }
+    }
+    let mut shared = lock(&shared);
+    if spectator {
+        shared.spectators -= 1;
+    } else {
+        shared.racers.retain(|r| r.id != id);
+    }
+    shared.clients.retain(|(client, _)| *client != id);
+    let lobby = shared.lobby();
+    shared.broadcast(&lobby);
+}
+
+fn update(shared: &mut Shared, id: u32, position: usize, wpm: u16, accuracy: u16,
+          finished_ms: Option<u64>) {
+    if let Some(racer) = shared.racers.iter_mut().find(|r| r.id == id) {
+        racer.position = position;
+        racer.wpm = wpm;
+        racer.accuracy = accuracy;
+        racer.finished_ms = finished_ms;
+    }
+    let lobby = shared.lobby();
+    shared.broadcast(&lobby);
+}
+
+fn lock(shared: &Mutex<Shared>) -> MutexGuard<'_, Shared> {
+    // a panicked network thread leaves nothing half-written worth refusing
+    shared.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn line(message: &Message) -> String {
+    // plain data, it always makes JSON
+    serde_json::to_string(message).unwrap_or_default()
+}
+
+fn send(stream: &mut TcpStream, message: &Message) -> io::Result<()> {
+    writeln!(stream, "{}", line(message))
+}
+
+fn read(reader: &mut impl BufRead) -> io::Result<Message> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed"));
+    }
+    serde_json::from_str(&line).map_err(io::Error::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    /// Waits for the text of a start to reach `race`.
+    fn wait_for_text(race: &Race) -> Option<String> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            if let Some(text) = race.take_text() {
+                return Some(text);
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        None
+    }
+
+    /// Waits for `done` to hold, false if it never does.
+    fn wait_until(done: impl Fn() -> bool) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            if done() {
+                return true;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        false
+    }
+
+    /// A host on a free port and the address to reach it on loopback.
+    fn host() -> (Race, String) {
+        let host = Race::host(0, "host").unwrap();
+        let port = host.address.rsplit(':').next().unwrap();
+        let address = format!("127.0.0.1:{port}");
+        (host, address)
+    }
+
+    fn names(racers: &[Racer]) -> Vec<&str> {
+        racers.iter().map(|r| r.name.as_str()).collect()
+    }
+
+    #[test]
+    fn progress_and_ranking() {
+        let (mut host, address) = host();
+        let mut slow = Race::join(&address, "slow").unwrap();
+        let mut fast = Race::join(&address, "fast").unwrap();
+        assert_eq!((slow.id, fast.id), (1, 2));
+        assert!(wait_until(|| fast.racers().len() == 3));
+
+        host.start("the race text");
+        assert_eq!(wait_for_text(&slow).as_deref(), Some("the race text"));
+        slow.send_progress(4, 30, 100, None);
+        fast.send_progress(13, 80, 95, Some(2500));
+        host.send_progress(8, 50, 100, None);
+        // the host takes in the progress and every lobby update gets out
+        let done = |race: &Race| race.racers().iter().map(|r| r.position).sum::<usize>() == 25;
+        assert!(wait_until(|| done(&host) && done(&slow) && done(&fast)));
+        let fast_racer = slow.racers().into_iter().find(|r| r.id == fast.id).unwrap();
+        assert_eq!((fast_racer.wpm, fast_racer.accuracy, fast_racer.finished_ms), (80, 95, Some(2500)));
+        // finished first, then by how far they got
+        assert_eq!(names(&host.ranking()), ["fast", "host", "slow"]);
+        assert_eq!(names(&slow.ranking()), ["fast", "host", "slow"]);
+    }
+
+    #[test]
+    fn a_client_that_stops_reading() {
+        let (host, address) = host();
+        let mut stalled = TcpStream::connect(&address).unwrap();
+        send(&mut stalled, &Message::Hello { name: "asleep".to_string(), spectator: false }).unwrap();
+        assert!(wait_until(|| host.racers().len() == 2));
+        // far more than the socket buffers hold, the host never waits on it
+        let text = "word ".repeat(20_000);
+        let started = Instant::now();
+        for _ in 0..200 {
+            host.start(&text);
+            host.racers();
+        }
+        assert!(started.elapsed() < Duration::from_secs(5));
+        let racer = Race::join(&address, "awake").unwrap();
+        assert_eq!(wait_for_text(&racer).map(|t| t.len()), Some(text.len()));
+    }
+
+    #[test]
+    fn joining_after_the_start() {
+        let (host, address) = host();
+        host.start("the race text");
+        // the host keeps its own test and the text for whoever comes late
+        assert_eq!(host.take_text(), None);
+
+        let racer = Race::join(&address, "late").unwrap();
+        assert_eq!(wait_for_text(&racer).as_deref(), Some("the race text"));
+        let spectator = Race::watch(&address, "screen").unwrap();
+        assert_eq!(wait_for_text(&spectator).as_deref(), Some("the race text"));
+        assert_eq!(host.spectators(), 1);
+        assert_eq!(host.take_text(), None);
+    }
+}
@@ -0,0 +1,231 @@
+// Text sources outside keybrah: an executable that reads one JSON request
+// on stdin and answers with one JSON text on stdout, written in whatever
+// language suits it.
+
+use std::{
+    error::Error,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::source::{TextMeta, TextRequest, TextSource};
+
+/// How long a plugin gets to answer before it is killed.
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What a plugin gets on stdin.
+#[derive(Serialize)]
+struct PluginRequest<'a> {
+    mode: &'a str,
+    /// Number of words.
+    length: usize,
+    seed: u64,
+    allowed_chars: &'a str,
+    punctuation: bool,
+    numbers: bool,
+}
+
+/// What a plugin answers on stdout, `error` instead of a text when it
+/// can't make one.
+#[derive(Deserialize)]
+struct PluginResponse {
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    language: Option<String>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+pub struct Plugin {
+    /// The file name of the executable, without extension.
+    pub id: String,
+    pub path: PathBuf,
+    pub timeout: Duration,
+}
+
+impl Plugin {
+    pub fn new(path: &Path) -> Plugin {
+        let id = path.file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.display().to_string());
+        Plugin { id, path: path.to_path_buf(), timeout: TIMEOUT }
+    }
+
+    /// Runs the plugin on `input` and returns its stdout.
+    fn run(&self, input: &[u8]) -> Result<String, Box<dyn Error>> {
+        let mut child = Command::new(&self.path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            // the terminal is drawn on stderr, plugins don't get to write there
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| format!("can't run {}: {err}", self.path.display()))?;
+        // a plugin that doesn't read its request is its own business
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = stdin.write_all(input);
+        }
+        // read on the side so a plugin that never ends can still be killed
+        let stdout = child.stdout.take().map(read_all);
+        let stderr = child.stderr.take().map(read_all);
+
+        let deadline = Instant::now() + self.timeout;
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if Instant::now() >= deadline {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("timed out after {} ms", self.timeout.as_millis()).into());
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+        // a child the plugin left behind may hold its pipes open, the
+        // deadline still holds
+        let output = |pipe: Option<Receiver<String>>| -> Result<String, Box<dyn Error>> {
+            let Some(pipe) = pipe else { return Ok(String::new()) };
+            pipe.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                .map_err(|_| format!("output still open after {} ms", self.timeout.as_millis()).into())
+        };
+        if !status.success() {
+            let stderr = output(stderr)?;
+            let reason = stderr.lines().next().unwrap_or("no output");
+            return Err(format!("{status}: {reason}").into());
+        }
+        output(stdout)
+    }
+}
+
+fn read_all<R: Read + Send + 'static>(mut pipe: R) -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut out = String::new();
+        let _ = pipe.read_to_string(&mut out);
+        let _ = sender.send(out);
+    });
+    receiver
+}
+
+impl TextSource for Plugin {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn name(&self) -> &str {
+        &self.id
+    }
+
+    fn language(&self) -> &str {
+        ""
+    }
+
+    fn generate(&self, request: &TextRequest) -> Result<(String, TextMeta), Box<dyn Error>> {
+        let input = serde_json::to_vec(&PluginRequest {
+            mode: request.mode,
+            length: request.words,
+            seed: request.seed,
+            allowed_chars: &request.allowed,
+            punctuation: request.options.punctuation,
+            numbers: request.options.numbers,
+        })?;
+        let output = self.run(&input)?;
+        let response: PluginResponse = serde_json::from_str(&output)
+            .map_err(|err| format!("bad response: {err}"))?;
+        if let Some(error) = response.error {
+            return Err(error.into());
+        }
+        // one line of words, however the plugin laid it out
+        let text = response.text.split_whitespace().collect::<Vec<_>>().join(" ");
+        let mut meta = self.meta(request.seed);
+        if let Some(name) = response.name {
+            meta.name = name;
+        }
+        if let Some(language) = response.language {
+            meta.language = language;
+        }
+        Ok((text, meta))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, os::unix::fs::PermissionsExt, process};
+
+    use super::*;
+    use crate::words::TextOptions;
+
+    /// A plugin running the shell `script`, given `timeout_ms` to answer.
+    fn plugin(name: &str, script: &str, timeout_ms: u64) -> Plugin {
+        let path = env::temp_dir().join(format!("keybrah-plugin-{}-{name}", process::id()));
+        fs::write(&path, format!("#!/bin/sh\n{script}\n")).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        let mut plugin = Plugin::new(&path);
+        plugin.timeout = Duration::from_millis(timeout_ms);
+        plugin
+    }
+
+    fn generate(plugin: &Plugin) -> Result<(String, TextMeta), String> {
+        let request = TextRequest {
+            mode: "words",
+            seed: 7,
+            words: 3,
+            options: TextOptions::default(),
+            allowed: "abc ".to_string(),
+        };
+        let result = plugin.generate(&request).map_err(|err| err.to_string());
+        let _ = fs::remove_file(&plugin.path);
+        result
+    }
+
+    #[test]
+    fn answers() {
+        let plugin = plugin("answers", r#"cat >/dev/null; echo '{"text": "  a  b   c ", "language": "xx"}'"#, 5000);
+        let (text, meta) = generate(&plugin).unwrap();
+        assert_eq!(text, "a b c");
+        assert_eq!(meta.language, "xx");
+        assert_eq!(meta.seed, Some(7));
+    }
+
+    #[test]
+    fn timeout() {
+        let plugin = plugin("timeout", "sleep 20", 300);
+        let started = Instant::now();
+        assert_eq!(generate(&plugin).unwrap_err(), "timed out after 300 ms");
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn child_holding_stdout() {
+        let plugin = plugin("child", "(sleep 20) &\necho '{\"text\": \"a\"}'", 300);
+        let started = Instant::now();
+        assert_eq!(generate(&plugin).unwrap_err(), "output still open after 300 ms");
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn failing() {
+        let plugin = plugin("failing", "echo 'no words today' >&2; echo more >&2; exit 3", 5000);
+        assert_eq!(generate(&plugin).unwrap_err(), "exit status: 3: no words today");
+    }
+
+    #[test]
+    fn bad_json() {
+        let plugin = plugin("bad_json", "echo 'a b c'", 5000);
+        assert!(generate(&plugin).unwrap_err().starts_with("bad response: "));
+    }
+
+    #[test]
+    fn error_field() {
+        let plugin = plugin("error_field", r#"echo '{"error": "no such language"}'"#, 5000);
+        assert_eq!(generate(&plugin).unwrap_err(), "no such language");
+    }
+}
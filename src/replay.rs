@@ -0,0 +1,259 @@
+// Timestamped keystrokes of finished tests, raced against as ghosts.
+
+use std::{error::Error, fs, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use keybrah::engine::{Engine, ErrorPolicy, Keystroke};
+
+use crate::ui::Wrapped;
+
+const REPLAYS: &str = "replays.json";
+
+/// Only the most recent runs are kept.
+pub const MAX_REPLAYS: usize = 100;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Replay {
+    pub text: String,
+    pub mode: String,
+    #[serde(default)]
+    pub policy: ErrorPolicy,
+    #[serde(default)]
+    pub blind: bool,
+    #[serde(default)]
+    pub geometry: String,
+    #[serde(default)]
+    pub layout: String,
+    pub wpm: u16,
+    #[serde(default)]
+    pub accuracy: u16,
+    #[serde(default)]
+    pub mistakes: u16,
+    pub failed: bool,
+    pub finished_at: u64,
+    pub keystrokes: Vec<Keystroke>,
+    /// When a timed test ran out, it ends there instead of on the last key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ended_ms: Option<u64>,
+}
+
+/// Tells a shared replay file apart from any other JSON.
+pub const FORMAT: &str = "keybrah-replay";
+/// Bumped whenever `ReplayFile` changes shape.
+pub const VERSION: u32 = 1;
+
+/// A replay as it is written to disk for sharing.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReplayFile {
+    pub format: String,
+    pub version: u32,
+    #[serde(flatten)]
+    pub replay: Replay,
+}
+
+/// Writes `replay` to `path` as a versioned replay file.
+pub fn export(replay: &Replay, path: &Path) -> Result<(), Box<dyn Error>> {
+    let file = ReplayFile {
+        format: FORMAT.to_string(),
+        version: VERSION,
+        replay: replay.clone(),
+    };
+    fs::write(path, serde_json::to_string_pretty(&file)?)?;
+    Ok(())
+}
+
+/// Reads a replay file, refusing anything that isn't one or comes from a
+/// newer version.
+pub fn import(path: &Path) -> Result<Replay, Box<dyn Error>> {
+    let file: ReplayFile = serde_json::from_str(&fs::read_to_string(path)?)?;
+    if file.format != FORMAT {
+        return Err(format!("{} is not a {FORMAT} file", path.display()).into());
+    }
+    if file.version > VERSION {
+        return Err(format!("{} is replay version {}, this build reads up to {VERSION}",
+            path.display(), file.version).into());
+    }
+    Ok(file.replay)
+}
+
+impl Replay {
+    /// How long the run took, from start to the last key or the end of
+    /// its time.
+    pub fn duration_ms(&self) -> u64 {
+        self.ended_ms
+            .or_else(|| self.keystrokes.last().map(|k| k.at_ms))
+            .unwrap_or(0)
+    }
+
+    /// Where the cursor of this run was `at_ms` into the test.
+    pub fn position_at(&self, at_ms: u64) -> usize {
+        self.keystrokes.iter()
+            .take_while(|k| k.at_ms <= at_ms)
+            .last()
+            .map(|k| k.position)
+            .unwrap_or(0)
+    }
+}
+
+/// Playback speeds the replay screen steps through.
+pub const SPEEDS: [f64; 3] = [0.5, 1.0, 2.0];
+
+/// How far one seek step jumps.
+pub const SEEK_MS: i64 = 2000;
+
+/// Plays a replay back in real time, `at_ms` is the point of the test
+/// being shown. Time comes in as readings of the app's clock.
+#[derive(Debug)]
+pub struct Player {
+    pub replay: Replay,
+    pub at_ms: u64,
+    pub speed: f64,
+    pub paused: bool,
+    /// The run as it stood at `at_ms`, the keys are pressed again as
+    /// playback reaches them.
+    pub engine: Engine,
+    /// The replay's text cut into rows for the playground.
+    pub wrapped: Wrapped,
+    // keystrokes of the replay already pressed into `engine`
+    pressed: usize,
+    last_tick_ms: u64,
+}
+
+impl Player {
+    pub fn new(replay: Replay, now_ms: u64) -> Player {
+        let engine = Engine::new(&replay.text, replay.policy);
+        Player {
+            replay,
+            at_ms: 0,
+            speed: 1.0,
+            paused: false,
+            engine,
+            wrapped: Wrapped::default(),
+            pressed: 0,
+            last_tick_ms: now_ms,
+        }
+    }
+
+    /// Moves playback on by the time since the last tick.
+    pub fn tick(&mut self, now_ms: u64) {
+        let elapsed = now_ms.saturating_sub(self.last_tick_ms) as f64;
+        self.last_tick_ms = now_ms;
+        if !self.paused && !self.finished() {
+            self.at_ms = (self.at_ms + (elapsed * self.speed) as u64)
+                .min(self.replay.duration_ms());
+            self.catch_up();
+        }
+    }
+
+    /// WPM of the run at `at_ms`.
+    pub fn wpm(&self) -> u16 {
+        self.engine.wpm_at(self.at_ms)
+    }
+
+    /// Presses the keys up to `at_ms`. The engine only goes forward, so
+    /// going back starts it over.
+    fn catch_up(&mut self) {
+        let keystrokes = &self.replay.keystrokes;
+        if self.pressed > 0 && keystrokes[self.pressed - 1].at_ms > self.at_ms {
+            self.engine.reset();
+            self.pressed = 0;
+        }
+        while let Some(keystroke) = keystrokes.get(self.pressed)
+            && keystroke.at_ms <= self.at_ms {
+            self.engine.press(keystroke.key, keystroke.at_ms);
+            self.pressed += 1;
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.at_ms >= self.replay.duration_ms()
+    }
+
+    pub fn toggle_pause(&mut self) {
+        // playing a finished replay starts it over
+        if self.paused && self.finished() {
+            self.at_ms = 0;
+            self.catch_up();
+        }
+        self.paused = !self.paused;
+    }
+
+    pub fn seek(&mut self, delta_ms: i64) {
+        self.at_ms = (self.at_ms as i64 + delta_ms)
+            .clamp(0, self.replay.duration_ms() as i64) as u64;
+        self.catch_up();
+    }
+
+    pub fn faster(&mut self) {
+        self.speed = SPEEDS.iter().copied().find(|s| *s > self.speed).unwrap_or(self.speed);
+    }
+
+    pub fn slower(&mut self) {
+        self.speed = SPEEDS.iter().rev().copied().find(|s| *s < self.speed).unwrap_or(self.speed);
+    }
+}
+
+/// Which earlier run on the same text the ghost replays.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GhostChoice {
+    Off,
+    Last,
+    Best,
+}
+
+impl GhostChoice {
+    pub fn next(&self) -> GhostChoice {
+        match self {
+            GhostChoice::Off => GhostChoice::Last,
+            GhostChoice::Last => GhostChoice::Best,
+            GhostChoice::Best => GhostChoice::Off,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GhostChoice::Off => "off",
+            GhostChoice::Last => "last",
+            GhostChoice::Best => "best",
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Replays {
+    pub replays: Vec<Replay>,
+}
+
+impl Replays {
+    /// Reads the replays stored in `dir`, a missing or unreadable file is
+    /// empty.
+    pub fn load(dir: Option<&Path>) -> Replays {
+        dir.and_then(|dir| fs::read_to_string(dir.join(REPLAYS)).ok())
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, dir: Option<&Path>) -> io::Result<()> {
+        let Some(dir) = dir else { return Ok(()) };
+        fs::create_dir_all(dir)?;
+        fs::write(dir.join(REPLAYS), serde_json::to_string(self)?)
+    }
+
+    pub fn push(&mut self, replay: Replay) {
+        self.replays.push(replay);
+        if self.replays.len() > MAX_REPLAYS {
+            self.replays.remove(0);
+        }
+    }
+
+    /// The run on `text` picked by `choice`, failed runs never race.
+    pub fn ghost(&self, text: &str, choice: GhostChoice) -> Option<&Replay> {
+        let mut runs = self.replays.iter().filter(|r| r.text == text && !r.failed);
+        match choice {
+            GhostChoice::Off => None,
+            GhostChoice::Last => runs.next_back(),
+            GhostChoice::Best => runs.max_by_key(|r| r.wpm),
+        }
+    }
+}
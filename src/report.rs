@@ -0,0 +1,29 @@
+// The last finished test with everything it was run with, printed as JSON
+// on stdout when keybrah exits so scripts can pick it up.
+
+use keybrah::engine::ErrorPolicy;
+use serde::Serialize;
+
+use crate::history::TestResult;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Report {
+    #[serde(flatten)]
+    pub result: TestResult,
+    /// From the start to the last key.
+    pub duration_ms: u64,
+    pub keystrokes: usize,
+    pub policy: ErrorPolicy,
+    pub blind: bool,
+    pub punctuation: bool,
+    pub numbers: bool,
+    /// Date of the daily challenge, if it was one.
+    pub daily: Option<String>,
+    pub source: String,
+    pub source_name: String,
+    pub language: String,
+    pub seed: Option<u64>,
+    pub geometry: String,
+    pub layout: String,
+    pub text: String,
+}
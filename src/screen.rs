@@ -0,0 +1,224 @@
+// What each screen does with a key and on the way in and out. Screens are
+// switched only through `App::go`, which checks the move against
+// `CurrentScreen::can_go` and runs the hooks here.
+
+use keybrah::engine::KeyInput;
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyEventKind};
+
+use crate::{
+    app::{App, CurrentScreen, CurrentlyEditing, Pace},
+    replay::SEEK_MS,
+    words,
+};
+
+/// What a key leads to.
+pub enum Transition {
+    Stay,
+    Go(CurrentScreen),
+    Quit,
+}
+
+pub trait Screen {
+    fn on_key(&self, app: &mut App, key: KeyEvent) -> Transition;
+
+    fn on_enter(&self, _app: &mut App) {}
+
+    fn on_exit(&self, _app: &mut App) {}
+}
+
+pub fn handler(screen: CurrentScreen) -> &'static dyn Screen {
+    match screen {
+        CurrentScreen::Main => &MainScreen,
+        CurrentScreen::Editing => &EditingScreen,
+        CurrentScreen::Stats => &StatsScreen,
+        CurrentScreen::Replay => &ReplayScreen,
+        CurrentScreen::Seed => &SeedScreen,
+        CurrentScreen::Exiting => &ExitingScreen,
+    }
+}
+
+/// Applies one key to the screen it was pressed on, true when it quits.
+pub fn handle_key(app: &mut App, key: KeyEvent) -> bool {
+    if key.kind == KeyEventKind::Release {
+        return false;
+    }
+    match handler(app.current_screen).on_key(app, key) {
+        Transition::Stay => false,
+        Transition::Go(next) => {
+            app.go(next);
+            false
+        }
+        Transition::Quit => true,
+    }
+}
+
+pub struct MainScreen;
+
+impl Screen for MainScreen {
+    fn on_key(&self, app: &mut App, key: KeyEvent) -> Transition {
+        match key.code {
+            KeyCode::Char('i') => app.begin(),
+            KeyCode::Char('q') => return Transition::Go(CurrentScreen::Exiting),
+            KeyCode::Char('g') => app.next_geometry(),
+            KeyCode::Char('c') => app.engine.set_policy(app.engine.policy().next()),
+            KeyCode::Char('b') => app.blind = !app.blind,
+            KeyCode::Char('a') => app.pace = app.pace.next(),
+            KeyCode::Char('h') => app.ghost_choice = app.ghost_choice.next(),
+            KeyCode::Char('d') => app.toggle_daily(),
+            KeyCode::Char('n') => {
+                app.set_seed(words::random_seed());
+            }
+            KeyCode::Char('u') => {
+                app.text_options.punctuation = !app.text_options.punctuation;
+                app.regenerate();
+            }
+            KeyCode::Char('m') => {
+                app.text_options.numbers = !app.text_options.numbers;
+                app.regenerate();
+            }
+            KeyCode::Char('s') => return Transition::Go(CurrentScreen::Seed),
+            KeyCode::Char('r') => app.watch_last_replay(),
+            KeyCode::Char('x') => app.export_replay(),
+            KeyCode::Char('+') => {
+                if let Pace::Wpm(wpm) = app.pace {
+                    app.pace = Pace::Wpm(wpm.saturating_add(5));
+                }
+            }
+            KeyCode::Char('-') => {
+                if let Pace::Wpm(wpm) = app.pace {
+                    app.pace = Pace::Wpm(wpm.saturating_sub(5).max(5));
+                }
+            }
+            _ => {}
+        }
+        Transition::Stay
+    }
+
+    fn on_enter(&self, app: &mut App) {
+        app.reset();
+    }
+}
+
+pub struct EditingScreen;
+
+impl Screen for EditingScreen {
+    fn on_key(&self, app: &mut App, key: KeyEvent) -> Transition {
+        if key.kind != KeyEventKind::Press {
+            return Transition::Stay;
+        }
+        let Some(editing) = &app.currently_editing else { return Transition::Stay };
+        match (key.code, editing) {
+            (KeyCode::Enter, CurrentlyEditing::Key) => {
+                app.currently_editing = Some(CurrentlyEditing::Value);
+            }
+            (KeyCode::Enter, CurrentlyEditing::Value) => {
+                app.save_key_value();
+                return Transition::Go(CurrentScreen::Main);
+            }
+            // only free typing has wrong chars to take back
+            (KeyCode::Backspace, CurrentlyEditing::Key) => app.press(KeyInput::Backspace),
+            (KeyCode::Backspace, CurrentlyEditing::Value) => {
+                app.value_input.pop();
+            }
+            (KeyCode::Esc, _) => return Transition::Go(CurrentScreen::Main),
+            (KeyCode::Tab, _) => app.toggle_editing(),
+            (KeyCode::Char(value), CurrentlyEditing::Key) => app.press(KeyInput::Char(value)),
+            _ => {}
+        }
+        Transition::Stay
+    }
+
+    fn on_enter(&self, app: &mut App) {
+        app.start_test();
+    }
+}
+
+pub struct StatsScreen;
+
+impl Screen for StatsScreen {
+    fn on_key(&self, app: &mut App, key: KeyEvent) -> Transition {
+        match key.code {
+            KeyCode::Char('i') => app.begin(),
+            KeyCode::Char('q') => return Transition::Go(CurrentScreen::Exiting),
+            KeyCode::Esc | KeyCode::Enter => return Transition::Go(CurrentScreen::Main),
+            KeyCode::Char('r') => app.watch_last_replay(),
+            KeyCode::Char('x') => app.export_replay(),
+            _ => {}
+        }
+        Transition::Stay
+    }
+
+    fn on_enter(&self, app: &mut App) {
+        app.save_result();
+    }
+}
+
+pub struct ReplayScreen;
+
+impl Screen for ReplayScreen {
+    fn on_key(&self, app: &mut App, key: KeyEvent) -> Transition {
+        if let KeyCode::Esc | KeyCode::Char('q') = key.code {
+            return Transition::Go(CurrentScreen::Main);
+        }
+        if key.code == KeyCode::Char('x') {
+            app.export_replay();
+        }
+        if let Some(player) = &mut app.player {
+            match key.code {
+                KeyCode::Char(' ') => player.toggle_pause(),
+                KeyCode::Left => player.seek(-SEEK_MS),
+                KeyCode::Right => player.seek(SEEK_MS),
+                KeyCode::Up => player.faster(),
+                KeyCode::Down => player.slower(),
+                _ => {}
+            }
+        }
+        Transition::Stay
+    }
+
+    fn on_exit(&self, app: &mut App) {
+        app.player = None;
+    }
+}
+
+/// Prompt for the seed of the next text.
+pub struct SeedScreen;
+
+impl Screen for SeedScreen {
+    fn on_key(&self, app: &mut App, key: KeyEvent) -> Transition {
+        match key.code {
+            KeyCode::Char(digit) if digit.is_ascii_digit() && app.seed_input.len() < 20 => {
+                app.seed_input.push(digit);
+            }
+            KeyCode::Backspace => {
+                app.seed_input.pop();
+            }
+            KeyCode::Enter => {
+                // anything past u64 just isn't taken
+                if let Ok(seed) = app.seed_input.parse() {
+                    app.set_seed(seed);
+                    return Transition::Go(CurrentScreen::Main);
+                }
+            }
+            KeyCode::Esc => return Transition::Go(CurrentScreen::Main),
+            _ => {}
+        }
+        Transition::Stay
+    }
+
+    fn on_enter(&self, app: &mut App) {
+        app.seed_input.clear();
+    }
+}
+
+pub struct ExitingScreen;
+
+impl Screen for ExitingScreen {
+    fn on_key(&self, _app: &mut App, key: KeyEvent) -> Transition {
+        match key.code {
+            KeyCode::Char('y') => Transition::Quit,
+            KeyCode::Char('n') => Transition::Go(CurrentScreen::Main),
+            _ => Transition::Stay,
+        }
+    }
+}
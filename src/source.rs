@@ -0,0 +1,191 @@
+// Where test texts come from. Every source is a `TextSource` in the
+// `Sources` registry, the app only ever asks the registry for a text by
+// source id.
+
+use std::{error::Error, fs, io, path::Path};
+
+use crate::words::{self, TextOptions, ENGLISH_200};
+
+/// Source regular texts and the daily challenge come from.
+pub const DEFAULT_SOURCE: &str = "english";
+
+/// Where a text came from, kept next to it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TextMeta {
+    /// Key of the source in the registry.
+    pub id: String,
+    pub name: String,
+    /// Language tag like "en", empty when it doesn't apply.
+    pub language: String,
+    /// What the text was generated from, `None` when it can't be made
+    /// again (a race host's text, a replay).
+    pub seed: Option<u64>,
+}
+
+impl TextMeta {
+    /// For texts that came from outside any source.
+    pub fn other(name: &str) -> TextMeta {
+        TextMeta { id: name.to_string(), name: name.to_string(), ..TextMeta::default() }
+    }
+}
+
+/// What the app asks a source for.
+#[derive(Clone, Debug)]
+pub struct TextRequest {
+    /// What the text is for: "words", "time" (`words` is then enough to
+    /// last the time limit) or "daily".
+    pub mode: &'static str,
+    pub seed: u64,
+    pub words: usize,
+    pub options: TextOptions,
+    /// Every char the current keyboard can type.
+    pub allowed: String,
+}
+
+pub trait TextSource {
+    /// Stable key the source is registered and picked by.
+    fn id(&self) -> &str;
+
+    fn name(&self) -> &str;
+
+    fn language(&self) -> &str;
+
+    /// A text for `request` and where it came from, the same request
+    /// should give the same text.
+    fn generate(&self, request: &TextRequest) -> Result<(String, TextMeta), Box<dyn Error>>;
+
+    fn meta(&self, seed: u64) -> TextMeta {
+        TextMeta {
+            id: self.id().to_string(),
+            name: self.name().to_string(),
+            language: self.language().to_string(),
+            seed: Some(seed),
+        }
+    }
+}
+
+/// Random words from a fixed list, with punctuation and numbers mixed in
+/// as the options ask.
+pub struct WordList {
+    pub id: String,
+    pub name: String,
+    pub language: String,
+    pub words: Vec<String>,
+}
+
+impl WordList {
+    pub fn english() -> WordList {
+        WordList {
+            id: DEFAULT_SOURCE.to_string(),
+            name: "english 200".to_string(),
+            language: "en".to_string(),
+            words: ENGLISH_200.iter().map(|w| w.to_string()).collect(),
+        }
+    }
+}
+
+impl TextSource for WordList {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn language(&self) -> &str {
+        &self.language
+    }
+
+    fn generate(&self, request: &TextRequest) -> Result<(String, TextMeta), Box<dyn Error>> {
+        if self.words.is_empty() {
+            return Err(format!("{} has no words", self.name).into());
+        }
+        let list: Vec<&str> = self.words.iter().map(String::as_str).collect();
+        let text = words::generate_with(&list, request.seed, request.words, &request.options);
+        Ok((text, self.meta(request.seed)))
+    }
+}
+
+/// The words of a file, the whole file every time.
+pub struct FileSource {
+    pub name: String,
+    pub text: String,
+}
+
+impl FileSource {
+    /// Id `FileSource`s are registered under, there is only ever one.
+    pub const ID: &'static str = "file";
+
+    pub fn open(path: &Path) -> io::Result<FileSource> {
+        let text = fs::read_to_string(path)?;
+        let name = path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.display().to_string());
+        // one line of words, however the file lays them out
+        Ok(FileSource { name, text: text.split_whitespace().collect::<Vec<_>>().join(" ") })
+    }
+}
+
+impl TextSource for FileSource {
+    fn id(&self) -> &str {
+        FileSource::ID
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn language(&self) -> &str {
+        ""
+    }
+
+    fn generate(&self, _request: &TextRequest) -> Result<(String, TextMeta), Box<dyn Error>> {
+        // nothing random about it, so no seed to make it again from
+        Ok((self.text.clone(), TextMeta { seed: None, ..self.meta(0) }))
+    }
+}
+
+/// Every text source the app can pick from.
+pub struct Sources {
+    sources: Vec<Box<dyn TextSource>>,
+}
+
+impl Sources {
+    /// The sources that ship with keybrah.
+    pub fn builtin() -> Sources {
+        let mut sources = Sources { sources: vec![] };
+        sources.register(Box::new(WordList::english()));
+        sources
+    }
+
+    /// Adds `source`, replacing one registered under the same id.
+    pub fn register(&mut self, source: Box<dyn TextSource>) {
+        self.sources.retain(|s| s.id() != source.id());
+        self.sources.push(source);
+    }
+
+    pub fn get(&self, id: &str) -> Option<&dyn TextSource> {
+        self.sources.iter().find(|s| s.id() == id).map(|s| s.as_ref())
+    }
+
+    /// The source with id `name`, else the first one in language `name`.
+    pub fn find(&self, name: &str) -> Option<&dyn TextSource> {
+        self.get(name)
+            .or_else(|| self.sources.iter().find(|s| s.language() == name).map(|s| s.as_ref()))
+    }
+
+    pub fn ids(&self) -> Vec<&str> {
+        self.sources.iter().map(|s| s.id()).collect()
+    }
+
+    /// Asks the source `id` for a text and its metadata.
+    pub fn generate(&self, id: &str, request: &TextRequest) -> Result<(String, TextMeta), Box<dyn Error>> {
+        let source = self.get(id).ok_or_else(|| format!("no text source {id}"))?;
+        let (text, meta) = source.generate(request)?;
+        if text.trim().is_empty() {
+            return Err(format!("{} gave an empty text", source.name()).into());
+        }
+        Ok((text, meta))
+    }
+}
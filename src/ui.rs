@@ -1,3 +1,5 @@
+use std::{cell::{Ref, RefCell}, ops::Range};
+
 use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Style, Stylize},
@@ -5,9 +7,22 @@ use ratatui::{
     widgets::{Block, BorderType, Borders, Clear, Paragraph, Wrap}, Frame
 };
 
-use crate::app::{App, CurrentScreen};
+use keybrah::engine::{Cell, CellState, Engine};
+
+use crate::{
+    app::{App, CurrentScreen, GhostDelta},
+    keyboard::{Hint, Key},
+    net::{Race, Racer},
+};
 
 pub fn ui(frame: & mut Frame, app: &App) {
+    // the replay screen draws the run being played back instead of the test
+    let player = app.player.as_ref().filter(|_| app.current_screen == CurrentScreen::Replay);
+    let engine = player.map_or(&app.engine, |player| &player.engine);
+    let wrapped = player.map_or(&app.wrapped, |player| &player.wrapped);
+    let wpm = player.map_or(app.wpm, |player| Some(player.wpm()));
+    let blind = app.blind && player.is_none();
+
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
@@ -22,75 +37,121 @@ pub fn ui(frame: & mut Frame, app: &App) {
         .border_type(BorderType::Rounded)
         .style(Style::default());
 
-    let title = Paragraph::new(Text::styled(
-        "KBrah!", Style::default().fg(Color::Green),
-        ))
+    let mut title_line = vec![Span::styled("KBrah!", Style::default().fg(Color::Green))];
+    if let Some(status) = &app.status {
+        title_line.push(Span::styled(format!("  ·  {status}"),
+            Style::default().fg(Color::DarkGray)));
+    }
+    let title = Paragraph::new(Line::from(title_line))
         .centered()
         .block(title_block);
 
     frame.render_widget(title, chunks[0]);
 
+    // in a race the standings take the keyboard's place
+    let racers = app.race.as_ref().map(|race| race.racers());
+    let bottom_height = match &racers {
+        Some(racers) => racers.len() as u16 + 2,
+        None => app.keyboard.height() + 2,
+    };
+    let body_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Min(5),
+            Constraint::Length(bottom_height),
+        ])
+        .split(chunks[1]);
+
+    let body_title = match (&app.current_screen, app.replay_issues.is_empty()) {
+        (CurrentScreen::Replay, true) => Line::from("  PLAYGROUND · REPLAY ✓ verified  "),
+        (CurrentScreen::Replay, false) => Line::from(vec![
+            Span::raw("  PLAYGROUND · REPLAY "),
+            Span::styled(format!("✗ {}  ", app.replay_issues.join(", ")),
+                Style::default().fg(Color::LightRed)),
+        ]),
+        (CurrentScreen::Main, _) => Line::from(format!("  PLAYGROUND · {}today's best: {}  ",
+            match &app.daily {
+                Some(date) => format!("daily {date} · "),
+                None => String::new(),
+            },
+            match app.todays_best() {
+                Some(wpm) => format!("{wpm} wpm"),
+                None => "none yet".to_string(),
+            })),
+        _ => Line::from("  PLAYGROUND  "),
+    };
     let body_block = Block::default()
-        .title("  PLAYGROUND  ")
+        .title(body_title)
         .borders(Borders::ALL)
-        .padding(ratatui::widgets::Padding { left: 0, right: 0,
-        top: (chunks[1].height / 2) - 2,
-        bottom: 0 })
         .border_type(BorderType::Rounded)
         .style(Style::default());
 
 
-    let mut rest: Vec<char> = app.current_text.chars().collect();
-    let mut cursor = ' ';
-    if app.current_text.len() != 0 {
-        cursor = rest.remove(0);
-    } 
-    let rest_str = rest.clone().into_iter()
-        .map(|i| i.to_string())
-        .collect::<String>();
-    let style = match app.current_screen {
-        CurrentScreen::Editing => {
-            Style::default().fg(Color::Black).bg(Color::LightCyan)}, 
-        _ => { Style::default().fg(Color::Black).bg(Color::White)}, };
-    let cursor_span = if !(app.wrong) {
-        Span::styled(cursor.to_string(), style) } else {
-            Span::styled(cursor.to_string(),
-            Style::default().fg(Color::Black).bg(Color::LightRed))
-        };
-
-    let mut text = vec![cursor_span.clone(),
-    Span::styled(rest_str, Style::default().fg(Color::White))];
-
-    if app.key_input.len() != 0 {
-        let input = app.key_input.clone();
-        let mut the_rest: Vec<char> = app.current_text.chars().collect();
-        if the_rest.len() != 0 { the_rest.remove(0); } 
-        let new_str = the_rest.clone().into_iter()
-            .map(|i| i.to_string())
-            .collect::<String>();
-
-        text = vec![
-            Span::styled(input,
-                Style::default().fg(Color::LightGreen)),
-                cursor_span,
-                Span::styled(new_str,
-                    Style::default().fg(Color::White)),
-        ];
+    let cursor_style = match app.current_screen {
+        // blind mode never shows the cursor going red
+        _ if engine.wrong() && !blind => Style::default().fg(Color::Black).bg(Color::LightRed),
+        CurrentScreen::Editing | CurrentScreen::Replay => Style::default().fg(Color::Black).bg(Color::LightCyan),
+        _ => Style::default().fg(Color::Black).bg(Color::White),
     };
+    // everyone else's place in the text: spectated racers, pace and ghost
+    let mut carets = vec![];
+    if let (Some(race), Some(racers)) = (&app.race, &racers)
+        && race.spectating {
+        for (i, racer) in racers.iter().enumerate() {
+            let index = racer.position.min(engine.target().len().saturating_sub(1));
+            carets.push((index, RACER_COLORS[i % RACER_COLORS.len()]));
+        }
+    }
+    if matches!(app.current_screen, CurrentScreen::Editing) {
+        if let Some(pace) = app.pace_index() {
+            carets.push((pace, Color::DarkGray));
+        }
+        if let Some(ghost) = app.ghost_index() {
+            carets.push((ghost, Color::Magenta));
+        }
+    }
+    let text_lines = text_lines(app, engine, wrapped, blind, cursor_style, &carets,
+        body_chunks[0].width.saturating_sub(2) as usize);
+    let body_block = body_block.padding(ratatui::widgets::Padding { left: 0, right: 0,
+        top: body_chunks[0].height.saturating_sub(2 + text_lines.len() as u16) / 2,
+        bottom: 0 });
+    let text_thingy = Paragraph::new(text_lines).block(body_block);
+    frame.render_widget(text_thingy, body_chunks[0]);
+
+    if let (Some(race), Some(racers)) = (&app.race, &racers) {
+        let race_block = Block::default()
+            .title(format!("  RACE · {} {}{}  ",
+                match (race.is_host, race.spectating) {
+                    (true, _) => "hosting on",
+                    (false, true) => "watching",
+                    (false, false) => "joined",
+                },
+                race.address,
+                match race.spectators() {
+                    0 => String::new(),
+                    n => format!(" · {n} watching"),
+                }))
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded);
+        let width = body_chunks[1].width.saturating_sub(2);
+        let race_paragraph = Paragraph::new(race_lines(app, race, racers, width))
+            .block(race_block);
+        frame.render_widget(race_paragraph, body_chunks[1]);
+    } else {
+        let keyboard_block = Block::default()
+            .title(format!("  KEYBOARD · {} · {}  ",
+                app.keyboard.geometry.name(), app.keyboard.layout.name))
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded);
+        let keyboard = Paragraph::new(keyboard_lines(app, engine))
+            .centered()
+            .block(keyboard_block);
+        frame.render_widget(keyboard, body_chunks[1]);
+    }
 
-    let rnum_str = app.rights.clone().into_iter()
-        .map(|i| i.to_string())
-        .collect::<String>();
-    let lnum_str = app.lefts.clone().into_iter()
-        .map(|i| i.to_string())
-        .collect::<String>();
-    let text_thingy = Paragraph::new(vec![
-        Line::from(rnum_str.clone()).centered().fg(Color::LightYellow),
-        Line::from(text).centered(),
-        Line::from(lnum_str.clone()).centered().fg(Color::LightYellow),
-    ]).block(body_block);
-    frame.render_widget(text_thingy, chunks[1]);
-
+    // accuracy and the wpm of free typing (only right cells count) would
+    // both give away wrong keys in blind mode
+    let hidden = blind && app.current_screen == CurrentScreen::Editing;
     let current_navigation_text = vec![
         match app.current_screen {
             CurrentScreen::Main => {
@@ -100,19 +161,35 @@ pub fn ui(frame: & mut Frame, app: &App) {
                 Style::default().fg(Color::LightRed)),
             CurrentScreen::Exiting => Span::styled("NOPE",
                 Style::default().fg(Color::LightRed)),
+            CurrentScreen::Seed => Span::styled("  --SEED--  ",
+                Style::default().fg(Color::LightYellow)),
             CurrentScreen::Stats => Span::styled("  --PAUSED--  ",
                 Style::default().fg(Color::LightRed)),
+            CurrentScreen::Replay => Span::styled(match &app.player {
+                Some(player) => format!("  --REPLAY-- {:.1}s / {:.1}s  ",
+                    player.at_ms as f64 / 1000.0,
+                    player.replay.duration_ms() as f64 / 1000.0),
+                None => "  --REPLAY--  ".to_string(),
+            }, Style::default().fg(Color::LightMagenta)),
         }
     .to_owned(),
     // A white divider bar to separate the two sections
     Span::styled(" | ", Style::default().fg(Color::White)),
         {
-            if let Some(editing) = &app.currently_editing {
+            if player.is_some() {
+                Span::styled(format!("  {} / {}  ", engine.words_typed(), engine.word_count()),
+                    Style::default().fg(Color::Green))
+            } else if let Some(editing) = &app.currently_editing {
                 match editing {
+                    crate::app::CurrentlyEditing::Key if app.time_limit.is_some() => {
+                        let limit_ms = app.time_limit.unwrap_or(0).saturating_mul(1000);
+                        let left_ms = limit_ms.saturating_sub(app.elapsed_ms().unwrap_or(0));
+                        Span::styled(format!("  {}s left  ", left_ms.div_ceil(1000)),
+                            Style::default().fg(Color::Green))
+                    },
                     crate::app::CurrentlyEditing::Key => {
-                        let written: Vec<&str> = app.key_input.split(' ').collect();
-                        let words: Vec<&str> = app.original_text.split(' ').collect();
-                        Span::styled(format!("  {} / {}  ", written.len() - 1, words.len()),
+                        let written = engine.words_typed();
+                        Span::styled(format!("  {} / {}  ", written, engine.word_count()),
                             Style::default().fg(Color::Green))
                     },
                     crate::app::CurrentlyEditing::Value => {
@@ -120,26 +197,31 @@ pub fn ui(frame: & mut Frame, app: &App) {
                             Style::default().fg(Color::LightGreen))
                     },
                 }
+            } else if let Some(secs) = app.time_limit {
+                Span::styled(format!("  {secs}s  "), Style::default().fg(Color::DarkGray))
             } else {
-                let words: Vec<&str> = app.original_text.split(' ').collect();
-                Span::styled(format!("  0 / {}  ", words.len()),
+                Span::styled(format!("  0 / {}  ", engine.word_count()),
                 Style::default().fg(Color::DarkGray))
             }
         },
         Span::styled(" | ", Style::default().fg(Color::White)),
-        Span::styled(format!("  {} % ", app.accuracy),
+        Span::styled(if hidden { "  -- % ".to_string() } else { format!("  {} % ", engine.accuracy()) },
         Style::default().fg(match app.current_screen {
             CurrentScreen::Editing => Color::LightCyan,
             CurrentScreen::Main => Color::DarkGray,
             CurrentScreen::Stats => Color::DarkGray,
+            CurrentScreen::Replay => Color::LightMagenta,
+            CurrentScreen::Seed => Color::DarkGray,
             CurrentScreen::Exiting => Color::DarkGray})),
         Span::styled(" | ", Style::default().fg(Color::White)),
-        Span::styled(format!("  wpm: {:?}  ",
-                app.wpm.unwrap_or(0)),
+        Span::styled(if hidden { "  wpm: --  ".to_string() } else { format!("  wpm: {:?}  ",
+                wpm.unwrap_or(0)) },
         Style::default().fg(match app.current_screen {
             CurrentScreen::Editing => Color::LightCyan,
             CurrentScreen::Main => Color::DarkGray,
             CurrentScreen::Stats => Color::DarkGray,
+            CurrentScreen::Replay => Color::LightMagenta,
+            CurrentScreen::Seed => Color::DarkGray,
             CurrentScreen::Exiting => Color::DarkGray})),
         ];
 
@@ -151,19 +233,42 @@ pub fn ui(frame: & mut Frame, app: &App) {
     let current_keys_hint = {
         match app.current_screen {
             CurrentScreen::Main => Span::styled(
-                "  [k]eybindings  |  [p]rofile  ",
+                format!("  [k]eybindings  |  [p]rofile  |  [g]eometry  |  [c]orrection: {}  |  [b]lind: {}  |  p[a]ce: {}  |  g[h]ost: {}  |  [d]aily: {}  |  [n]ew text  |  [s]eed  |  p[u]nctuation: {}  |  nu[m]bers: {}  |  [r]eplay  ",
+                    app.engine.policy().name(), if app.blind { "on" } else { "off" }, app.pace.name(),
+                    app.ghost_choice.name(), if app.daily.is_some() { "on" } else { "off" },
+                    if app.text_options.punctuation { "on" } else { "off" },
+                    if app.text_options.numbers { "on" } else { "off" })
+                    + match &app.race {
+                        Some(race) if race.is_host => "|  [i] start the race  ",
+                        Some(race) if race.spectating => "|  watching  ",
+                        Some(_) => "|  waiting for the host  ",
+                        None => "",
+                    },
                 Style::default().fg(Color::Red),
                 ),
             CurrentScreen::Editing => Span::styled(
                 "[ESC] Normal / (Tab) to switch boxes/ (enter) to complete",
                 Style::default().fg(Color::Red),
                 ),
+            CurrentScreen::Seed => Span::styled(
+                "  [0-9] seed  |  [⏎] generate  |  [esc] back  ",
+                Style::default().fg(Color::Red),
+                ),
             CurrentScreen::Exiting => Span::styled(
                 "(q) to quit / (e) to make new pair",
                 Style::default().fg(Color::Red),
                 ),
             CurrentScreen::Stats => Span::styled(
-                "Whatever",
+                "  [⏎] ok  |  [r]eplay  |  e[x]port  ",
+                Style::default().fg(Color::Red),
+                ),
+            CurrentScreen::Replay => Span::styled(
+                match &app.player {
+                    Some(player) => format!(
+                        "  [space] {}  |  ←/→ seek  |  ↑/↓ speed: {}×  |  e[x]port  |  [esc] back  ",
+                        if player.paused { "play" } else { "pause" }, player.speed),
+                    None => "  [esc] back  ".to_string(),
+                },
                 Style::default().fg(Color::Red),
                 ),
         }
@@ -185,6 +290,24 @@ pub fn ui(frame: & mut Frame, app: &App) {
     match &app.current_screen {
         CurrentScreen::Main => {},
         CurrentScreen::Editing => {},
+        CurrentScreen::Replay => {},
+        CurrentScreen::Seed => {
+            let popup_block = Block::default()
+                .title(" SEED ")
+                .title_alignment(ratatui::layout::Alignment::Center)
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded);
+            let seed_paragraph = Paragraph::new(vec![
+                Line::default(),
+                Line::from(vec![
+                    Span::raw(app.seed_input.clone()),
+                    Span::styled(" ", Style::default().bg(Color::LightYellow)),
+                ]),
+            ]).centered().block(popup_block);
+            let area = centered_rect(30, 20, frame.area());
+            frame.render_widget(Clear, area);
+            frame.render_widget(seed_paragraph, area);
+        },
         CurrentScreen::Exiting => {
             // frame.render_widget(Clear, frame.area());
             let popup_block = Block::default()
@@ -268,19 +391,69 @@ pub fn ui(frame: & mut Frame, app: &App) {
                 .to_string().clone()).block(wpm_block).centered();
             frame.render_widget(wpm_text, row_one_chunks[0]);
 
-            let acc_text = Paragraph::new(app.accuracy
+            let acc_text = Paragraph::new(app.engine.accuracy()
                 .to_string().clone()).block(acc_block).centered();
             frame.render_widget(acc_text, row_one_chunks[1]);
 
-            let words: Vec<&str> = app.original_text.split(' ').collect();
-            let words_text = Paragraph::new(words.len()
-                .to_string().clone()).block(words_block).centered();
+            let words_text = Paragraph::new(app.engine.word_count()
+                .to_string()).block(words_block).centered();
             frame.render_widget(words_text, row_one_chunks[2]);
 
-            let mis_text = Paragraph::new(app.mistakes
+            let mis_text = Paragraph::new(app.engine.mistakes()
                 .to_string().clone()).block(mis_block).centered();
             frame.render_widget(mis_text, row_one_chunks[3]);
 
+            let policy_text = if app.engine.failed() {
+                Line::from(vec![
+                    Span::styled(format!("{}  ", app.engine.policy().name()),
+                        Style::default().fg(Color::DarkGray)),
+                    Span::styled("FAILED", Style::default().fg(Color::LightRed)),
+                ])
+            } else {
+                Line::from(Span::styled(app.engine.policy().name(),
+                    Style::default().fg(Color::DarkGray)))
+            };
+            let mut details = vec![policy_text];
+            if let Some(seed) = app.text_meta.seed {
+                details.push(Line::from(Span::styled(
+                    format!("{} · seed {seed} · {}", app.text_meta.name, app.text_settings()),
+                    Style::default().fg(Color::DarkGray))));
+            }
+            if let Some(delta) = app.ghost_delta() {
+                let (amount, delta) = match delta {
+                    GhostDelta::Ms(ms) => (format!("{:.2}s", ms.unsigned_abs() as f64 / 1000.0), ms),
+                    GhostDelta::Chars(chars) => (format!("{} chars", chars.unsigned_abs()), chars),
+                };
+                let (word, color) = if delta <= 0 {
+                    ("ahead of", Color::LightGreen)
+                } else {
+                    ("behind", Color::LightRed)
+                };
+                details.push(Line::from(Span::styled(
+                    format!("{amount} {word} the ghost"),
+                    Style::default().fg(color))));
+            }
+            if let Some(race) = &app.race {
+                for (place, racer) in race.ranking().iter().enumerate() {
+                    let result = match racer.finished_ms {
+                        Some(ms) => format!("{:.2}s", ms as f64 / 1000.0),
+                        None => "racing".to_string(),
+                    };
+                    let color = if racer.id == race.id { Color::LightCyan } else { Color::Gray };
+                    details.push(Line::from(Span::styled(
+                        format!("{}. {}  {}  {} wpm", place + 1, racer.name, result, racer.wpm),
+                        Style::default().fg(color))));
+                }
+            }
+            details.push(Line::default());
+            if app.blind {
+                // the feedback blind mode held back during the test
+                let width = popup_chunks[1].width.saturating_sub(2) as usize;
+                details.extend(diff_lines(app, width));
+            }
+            let details_paragraph = Paragraph::new(details).centered();
+            frame.render_widget(details_paragraph, popup_chunks[1]);
+
             let row_four_block = Block::default()
                 .borders(Borders::NONE)
                 .border_type(BorderType::Rounded);
@@ -307,6 +480,227 @@ pub fn ui(frame: & mut Frame, app: &App) {
     }
 }
 
+/// Spectators tell the racers apart by these.
+const RACER_COLORS: [Color; 6] = [
+    Color::LightCyan, Color::LightMagenta, Color::LightYellow,
+    Color::LightGreen, Color::LightBlue, Color::LightRed,
+];
+
+/// One progress bar per racer, the local racer is highlighted. Spectators
+/// get every racer in their own colour instead.
+fn race_lines(app: &App, race: &Race, racers: &[Racer], width: u16) -> Vec<Line<'static>> {
+    let total = app.engine.target().len().max(1);
+    let name_width = racers.iter().map(|r| r.name.chars().count()).max().unwrap_or(0);
+    // name, bar, then "100%  123 wpm  100%  ✓ 12.34s"
+    let bar_width = (width as usize).saturating_sub(name_width + 34).max(10);
+    racers.iter().enumerate().map(|(i, racer)| {
+        let done = if racer.finished_ms.is_some() { total } else { racer.position.min(total) };
+        let filled = done * bar_width / total;
+        let color = if race.spectating {
+            RACER_COLORS[i % RACER_COLORS.len()]
+        } else if racer.id == race.id {
+            Color::LightCyan
+        } else {
+            Color::Gray
+        };
+        let finish = racer.finished_ms
+            .map(|ms| format!("  ✓ {:.2}s", ms as f64 / 1000.0))
+            .unwrap_or_default();
+        Line::from(vec![
+            Span::styled(format!(" {:<name_width$} ", racer.name), Style::default().fg(color)),
+            Span::styled("█".repeat(filled), Style::default().fg(color)),
+            Span::styled("░".repeat(bar_width - filled), Style::default().fg(Color::DarkGray)),
+            Span::styled(format!(" {:>3}%  {:>3} wpm  {:>3}%{finish}",
+                done * 100 / total, racer.wpm, racer.accuracy), Style::default().fg(color)),
+        ])
+    }).collect()
+}
+
+/// Lines of text the playground shows at once. The line being typed stays
+/// second, so the one just finished is still in view.
+const VISIBLE_LINES: usize = 3;
+
+/// Row boundaries of the text cut to one width, as (width, rows, widest
+/// row). Cut on the first frame at a width and kept until the width or
+/// the text changes, not on every frame.
+#[derive(Debug, Default)]
+pub struct Wrapped(RefCell<(usize, Vec<Range<usize>>, usize)>);
+
+impl Wrapped {
+    /// Forgets the rows, for when the text changes.
+    pub fn clear(&self) {
+        self.0.take();
+    }
+
+    fn rows(&self, text: &[char], width: usize) -> Ref<'_, (usize, Vec<Range<usize>>, usize)> {
+        let width = width.max(1);
+        if self.0.borrow().0 != width {
+            let rows = wrap(text, width);
+            let widest = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+            *self.0.borrow_mut() = (width, rows, widest);
+        }
+        self.0.borrow()
+    }
+}
+
+/// Word-wraps the text and its hint rows to `width` and keeps the
+/// `VISIBLE_LINES` rows around the cursor. Every row starts at the same
+/// column, so a text that fits on one row stays centered. The rows come
+/// from `wrapped`, only the rows shown are looked at, so a long text
+/// draws as fast as a short one. Extras that push a row past `width`
+/// wrap within it.
+fn text_lines(app: &App, engine: &Engine, wrapped: &Wrapped, blind: bool, cursor_style: Style,
+    carets: &[(usize, Color)], width: usize) -> Vec<Line<'static>> {
+    let cut = wrapped.rows(engine.target(), width);
+    let (rows, widest) = (&cut.1, cut.2);
+    let position = engine.position();
+    let active = rows.partition_point(|row| row.end <= position).min(rows.len() - 1);
+    let first = active.saturating_sub(1);
+    // the cells of the rows around the cursor, blind mode keeps extras
+    // out of sight
+    let mut shown: Vec<Vec<Cell>> = vec![];
+    for row in rows.iter().skip(first).take(VISIBLE_LINES + 1) {
+        let cells: Vec<Cell> = engine.shown(row.clone())
+            .filter(|cell| !(blind && cell.state == CellState::Extra))
+            .collect();
+        let chars: Vec<char> = cells.iter().map(|cell| cell.ch).collect();
+        shown.extend(wrap(&chars, width).into_iter().map(|part| cells[part].to_vec()));
+    }
+    let at = |row: &Vec<Cell>, index: usize| row.iter().any(|cell| cell.target == Some(index));
+    let active = shown.iter().position(|row| at(row, position)).unwrap_or(shown.len() - 1);
+    let margin = " ".repeat(width.saturating_sub(widest) / 2);
+    // only the cursor's char gets its finger hints
+    let (right, left) = engine.next_char().map_or((' ', ' '), |c| app.keyboard.fingers(c));
+    let hints = |finger: char, row: &[Cell]| -> String {
+        let hint = |cell: &Cell| match cell.target {
+            Some(t) if t == position => finger,
+            _ => ' ',
+        };
+        format!("{margin}{}", row.iter().map(hint).collect::<String>())
+    };
+
+    let mut lines = vec![];
+    for row in shown.iter().skip(active.saturating_sub(1)).take(VISIBLE_LINES) {
+        lines.push(Line::from(hints(right, row)).fg(Color::LightYellow));
+        let mut text = vec![Span::raw(margin.clone())];
+        for cell in row {
+            let mut style = match cell.target {
+                Some(t) if t == position => cursor_style,
+                _ => cell_style(blind, cell),
+            };
+            if let Some((_, color)) = carets.iter()
+                .find(|(index, _)| *index != position && cell.target == Some(*index)) {
+                style = style.bg(*color);
+            }
+            text.push(Span::styled(cell.ch.to_string(), style));
+        }
+        lines.push(Line::from(text));
+        lines.push(Line::from(hints(left, row)).fg(Color::LightYellow));
+    }
+    lines
+}
+
+fn cell_style(blind: bool, cell: &Cell) -> Style {
+    match cell.state {
+        CellState::Correct | CellState::Wrong if blind => Style::default().fg(Color::Gray),
+        CellState::Correct => Style::default().fg(Color::LightGreen),
+        CellState::Wrong => Style::default().fg(Color::LightRed),
+        CellState::Extra => Style::default().fg(Color::Red).crossed_out(),
+        CellState::Pending => Style::default().fg(Color::White),
+    }
+}
+
+/// Cuts the chars into rows of at most `width`, breaking after a space,
+/// or mid-word when a word is wider than a row.
+fn wrap(chars: &[char], width: usize) -> Vec<std::ops::Range<usize>> {
+    let width = width.max(1);
+    let mut rows = vec![];
+    let mut start = 0;
+    while chars.len() - start > width {
+        let end = (start..start + width).rev()
+            .find(|&i| chars[i] == ' ')
+            .map(|i| i + 1)
+            .unwrap_or(start + width);
+        rows.push(start..end);
+        start = end;
+    }
+    rows.push(start..chars.len());
+    rows
+}
+
+/// Typed text over expected text, column by column, cut into rows of
+/// `width` so long texts still line up.
+fn diff_lines(app: &App, width: usize) -> Vec<Line<'static>> {
+    let green = Style::default().fg(Color::LightGreen);
+    let red = Style::default().fg(Color::LightRed);
+    let plain = Style::default().fg(Color::DarkGray);
+    // (typed, expected, style) for every column
+    let columns: Vec<(char, char, Style)> = if app.engine.policy().free_typing() {
+        app.engine.shown(0..app.engine.target().len()).map(|cell| match cell.state {
+            CellState::Correct => (cell.ch, cell.ch, green),
+            CellState::Wrong => (cell.typed.unwrap_or(' '), cell.ch, red),
+            CellState::Extra => (cell.ch, ' ', red),
+            CellState::Pending => (' ', cell.ch, plain),
+        }).collect()
+    } else {
+        app.engine.target().iter().enumerate().map(|(i, ch)| {
+            match app.engine.wrong_keys().iter().find(|(index, _)| *index == i) {
+                Some((_, wrong)) => (*wrong, *ch, red),
+                None => (*ch, *ch, green),
+            }
+        }).collect()
+    };
+
+    let mut lines = vec![];
+    for chunk in columns.chunks(width.max(1)) {
+        let typed: Vec<Span> = chunk.iter()
+            .map(|(typed, _, style)| Span::styled(typed.to_string(), *style))
+            .collect();
+        let expected: String = chunk.iter().map(|(_, expected, _)| *expected).collect();
+        lines.push(Line::from(typed));
+        lines.push(Line::from(Span::styled(expected, plain)));
+    }
+    lines
+}
+
+/// Draws the keyboard one row per line, lighting up the key for the next
+/// char and the modifier that has to be held with it.
+fn keyboard_lines(app: &App, engine: &Engine) -> Vec<Line<'static>> {
+    let keyboard = &app.keyboard;
+    let hint = engine.next_char().and_then(|c| keyboard.hint(c));
+    let first_row = keyboard.keys.iter().map(|k| k.row).min().unwrap_or(0);
+    // every row is padded to the full board width so centering keeps them aligned
+    let board_width = keyboard.keys.iter().map(|k| k.x + k.width).max().unwrap_or(0);
+    let mut lines = vec![];
+    for row in first_row..first_row + keyboard.height() {
+        let mut keys: Vec<(usize, &Key)> = keyboard.keys.iter().enumerate()
+            .filter(|(_, k)| k.row == row)
+            .collect();
+        keys.sort_by_key(|(_, k)| k.x);
+        let mut spans = vec![];
+        let mut column = 0;
+        for (i, key) in keys {
+            if key.x > column {
+                spans.push(Span::raw(" ".repeat((key.x - column) as usize)));
+            }
+            let width = key.width.saturating_sub(1) as usize;
+            let style = match hint {
+                Some(h) if h.key == i => Style::default().fg(Color::Black).bg(Color::LightCyan),
+                Some(Hint { modifier: Some((_, m)), .. }) if m == i =>
+                    Style::default().fg(Color::Black).bg(Color::LightYellow),
+                _ => Style::default().fg(Color::White).bg(Color::DarkGray),
+            };
+            let label: String = key.label.chars().take(width).collect();
+            spans.push(Span::styled(format!("{label:^width$}"), style));
+            spans.push(Span::raw(" "));
+            column = key.x + key.width;
+        }
+        spans.push(Span::raw(" ".repeat(board_width.saturating_sub(column) as usize)));
+        lines.push(Line::from(spans));
+    }
+    lines
+}
+
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     // Cut vertically to 3 portions
     let popup_layout = Layout::default()
@@ -328,3 +722,28 @@ fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
         ])
         .split(popup_layout[1])[1]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(text: &str, width: usize) -> Vec<String> {
+        let chars: Vec<char> = text.chars().collect();
+        wrap(&chars, width).into_iter().map(|row| chars[row].iter().collect()).collect()
+    }
+
+    #[test]
+    fn wraps_at_spaces() {
+        assert_eq!(rows("the quick brown fox", 10), ["the quick ", "brown fox"]);
+        assert_eq!(rows("the quick brown fox", 30), ["the quick brown fox"]);
+        // the space ending a row stays on it
+        assert_eq!(rows("abcd efgh", 5), ["abcd ", "efgh"]);
+        assert_eq!(rows("", 5), [""]);
+    }
+
+    #[test]
+    fn breaks_a_word_wider_than_the_row() {
+        assert_eq!(rows("a abcdefghij b", 4), ["a ", "abcd", "efgh", "ij b"]);
+        assert_eq!(rows("abc", 0), ["a", "b", "c"]);
+    }
+}
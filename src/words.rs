@@ -0,0 +1,237 @@
+// Generated texts: a list of common words and a seeded generator, so the
+// same seed gives the same text on every machine.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Random seeds stay below this, any `u64` still works when entered.
+pub const MAX_RANDOM_SEED: u64 = 1_000_000;
+
+/// The 200 most common English words, lowercase.
+pub const ENGLISH_200: [&str; 200] = [
+    "the", "be", "of", "and", "a", "to", "in", "he", "have", "it",
+    "that", "for", "they", "with", "as", "not", "on", "she", "at", "by",
+    "this", "we", "you", "do", "but", "from", "or", "which", "one", "would",
+    "all", "will", "there", "say", "who", "make", "when", "can", "more", "if",
+    "no", "man", "out", "other", "so", "what", "time", "up", "go", "about",
+    "than", "into", "could", "state", "only", "new", "year", "some", "take", "come",
+    "these", "know", "see", "use", "get", "like", "then", "first", "any", "work",
+    "now", "may", "such", "give", "over", "think", "most", "even", "find", "day",
+    "also", "after", "way", "many", "must", "look", "before", "great", "back", "through",
+    "long", "where", "much", "should", "well", "people", "down", "own", "just", "because",
+    "good", "each", "those", "feel", "seem", "how", "high", "too", "place", "little",
+    "world", "very", "still", "nation", "hand", "old", "life", "tell", "write", "become",
+    "here", "show", "house", "both", "between", "need", "mean", "call", "develop", "under",
+    "last", "right", "move", "thing", "general", "school", "never", "same", "another", "begin",
+    "while", "number", "part", "turn", "real", "leave", "might", "want", "point", "form",
+    "off", "child", "few", "small", "since", "against", "ask", "late", "home", "interest",
+    "large", "person", "end", "open", "public", "follow", "during", "present", "without", "again",
+    "hold", "govern", "around", "possible", "head", "consider", "word", "program", "problem", "however",
+    "lead", "system", "set", "order", "eye", "plan", "run", "keep", "face", "fact",
+    "group", "play", "stand", "increase", "early", "course", "change", "help", "line", "city",
+];
+
+/// splitmix64, small and identical on every platform.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..n`.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// True `percent` times out of a hundred.
+    pub fn chance(&mut self, percent: u8) -> bool {
+        self.below(100) < percent as usize
+    }
+}
+
+/// What goes into generated text besides lowercase words. Rates are the
+/// percent of words that get each.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TextOptions {
+    /// Capitalized sentences with commas, periods, quotes and parentheses.
+    pub punctuation: bool,
+    pub numbers: bool,
+    pub comma_rate: u8,
+    pub period_rate: u8,
+    pub quote_rate: u8,
+    pub paren_rate: u8,
+    pub number_rate: u8,
+}
+
+impl Default for TextOptions {
+    fn default() -> TextOptions {
+        TextOptions {
+            punctuation: false,
+            numbers: false,
+            comma_rate: 10,
+            period_rate: 12,
+            quote_rate: 4,
+            paren_rate: 3,
+            number_rate: 10,
+        }
+    }
+}
+
+impl TextOptions {
+    /// Tells results of plain and dressed-up texts apart.
+    pub fn name(&self) -> Option<&'static str> {
+        match (self.punctuation, self.numbers) {
+            (false, false) => None,
+            (true, false) => Some("punctuation"),
+            (false, true) => Some("numbers"),
+            (true, true) => Some("punctuation + numbers"),
+        }
+    }
+
+    /// The rates that differ from the defaults and apply, like
+    /// "comma 20%", `None` when there are none.
+    pub fn rates_name(&self) -> Option<String> {
+        let default = TextOptions::default();
+        let rates = [
+            (self.punctuation, "comma", self.comma_rate, default.comma_rate),
+            (self.punctuation, "period", self.period_rate, default.period_rate),
+            (self.punctuation, "quote", self.quote_rate, default.quote_rate),
+            (self.punctuation, "paren", self.paren_rate, default.paren_rate),
+            (self.numbers, "number", self.number_rate, default.number_rate),
+        ];
+        let changed: Vec<String> = rates.iter()
+            .filter(|(on, _, rate, default)| *on && rate != default)
+            .map(|(_, name, rate, _)| format!("{name} {rate}%"))
+            .collect();
+        (!changed.is_empty()).then(|| changed.join(", "))
+    }
+}
+
+/// `count` words picked from `list` by `seed`, never the same word twice
+/// in a row, with punctuation and numbers mixed in as `options` asks. The
+/// same seed and options always give the same text.
+pub fn generate_with(list: &[&str], seed: u64, count: usize, options: &TextOptions) -> String {
+    let mut rng = Rng::new(seed);
+    let mut words: Vec<String> = Vec::with_capacity(count);
+    let mut last = "";
+    let mut sentence_start = true;
+    while words.len() < count {
+        let picked = list[rng.below(list.len())];
+        if picked == last && list.len() > 1 {
+            continue;
+        }
+        last = picked;
+        let mut word = if options.numbers && rng.chance(options.number_rate) {
+            // one to four digits
+            let digits = 1 + rng.below(4) as u32;
+            rng.below(10usize.pow(digits)).to_string()
+        } else {
+            picked.to_string()
+        };
+        if options.punctuation {
+            if sentence_start {
+                word = capitalize(&word);
+                sentence_start = false;
+            }
+            if rng.chance(options.quote_rate) {
+                word = format!("\"{word}\"");
+            } else if rng.chance(options.paren_rate) {
+                word = format!("({word})");
+            }
+            if words.len() + 1 == count || rng.chance(options.period_rate) {
+                word.push('.');
+                sentence_start = true;
+            } else if rng.chance(options.comma_rate) {
+                word.push(',');
+            }
+        }
+        words.push(word);
+    }
+    words.join(" ")
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// A fresh seed short enough to read off the Stats screen and type back.
+pub fn random_seed() -> u64 {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0);
+    Rng::new(nanos).next_u64() % MAX_RANDOM_SEED
+}
+
+/// A seed for a string, e.g. the date of a daily challenge (FNV-1a).
+pub fn seed_from(s: &str) -> u64 {
+    s.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn punctuated() -> TextOptions {
+        TextOptions { punctuation: true, ..TextOptions::default() }
+    }
+
+    #[test]
+    fn same_seed_same_text() {
+        let options = TextOptions { punctuation: true, numbers: true, ..TextOptions::default() };
+        let text = generate_with(&ENGLISH_200, 42, 50, &options);
+        assert_eq!(text, generate_with(&ENGLISH_200, 42, 50, &options));
+        assert_ne!(text, generate_with(&ENGLISH_200, 43, 50, &options));
+        assert_eq!(text.split(' ').count(), 50);
+        // pinned, a seed typed on another machine gives this very text
+        assert_eq!(generate_with(&ENGLISH_200, 42, 5, &TextOptions::default()), "with where take public than");
+    }
+
+    #[test]
+    fn punctuation_makes_sentences() {
+        for seed in 0..20 {
+            let text = generate_with(&ENGLISH_200, seed, 40, &punctuated());
+            assert!(text.ends_with('.'), "{text}");
+            let mut sentence_start = true;
+            for word in text.split(' ') {
+                let first = word.trim_start_matches(['"', '(']).chars().next().unwrap();
+                assert_eq!(first.is_uppercase(), sentence_start, "{word} in {text}");
+                sentence_start = word.ends_with('.');
+            }
+        }
+    }
+
+    #[test]
+    fn a_rate_of_0_never_adds_the_mark() {
+        let options = TextOptions {
+            numbers: true,
+            comma_rate: 0,
+            quote_rate: 0,
+            paren_rate: 0,
+            number_rate: 0,
+            ..punctuated()
+        };
+        let no_periods = TextOptions { period_rate: 0, ..punctuated() };
+        for seed in 0..20 {
+            let text = generate_with(&ENGLISH_200, seed, 100, &options);
+            assert!(!text.contains([',', '"', '(', ')']), "{text}");
+            assert!(!text.contains(|c: char| c.is_ascii_digit()), "{text}");
+            // only the last word ends the one sentence
+            let text = generate_with(&ENGLISH_200, seed, 100, &no_periods);
+            assert_eq!(text.matches('.').count(), 1, "{text}");
+        }
+    }
+}